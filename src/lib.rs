@@ -1,25 +1,121 @@
+#![allow(non_camel_case_types, non_snake_case)]
+
 use std::cmp::PartialEq;
-use std::collections::HashMap;
 use serde::Serialize;
 use crate::Color::{BLACK, WHITE};
-use crate::Move::{CAPTURE, REGULAR};
-use crate::Status::{BLACK_TO_MOVE, WHITE_TO_MOVE};
-use std::io::*;
+use crate::PieceKind::{BISHOP, KING, KNIGHT, PAWN, QUEEN, ROOK};
+use crate::CastleSide::{LONG, SHORT};
+use crate::DrawReason::{DEAD_POSITION, FIFTY_MOVE_RULE, FIVEFOLD_REPETITION, INSUFFICIENT_MATERIAL, SEVENTY_FIVE_MOVE_RULE, THREEFOLD_REPETITION};
+use crate::Status::{BLACK_CAN_CLAIM_DRAW, BLACK_HAS_CHECKMATE, BLACK_TO_MOVE, DRAW, STALEMATE, WHITE_CAN_CLAIM_DRAW, WHITE_HAS_CHECKMATE, WHITE_TO_MOVE};
+use std::io::stdin;
+
+mod pgn;
+mod san;
+mod tree;
+mod uci;
+mod zobrist;
+
+pub use pgn::{read_pgn, PgnGame, PgnMove};
+pub use tree::{GameTree, Node, NodeId};
 
 const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR";
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Status {
     WHITE_TO_MOVE,
     BLACK_TO_MOVE,
-    DRAW,
+    DRAW(DrawReason),
+    STALEMATE,
     WHITE_HAS_CHECKMATE,
     BLACK_HAS_CHECKMATE,
+    // The game goes on, but the side to move may claim a draw for the reason
+    WHITE_CAN_CLAIM_DRAW(DrawReason),
+    BLACK_CAN_CLAIM_DRAW(DrawReason),
 }
 
-pub type ChessBoard = Vec<Vec<char>>;
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DrawReason {
+    FIFTY_MOVE_RULE,
+    SEVENTY_FIVE_MOVE_RULE,
+    THREEFOLD_REPETITION,
+    FIVEFOLD_REPETITION,
+    INSUFFICIENT_MATERIAL,
+    DEAD_POSITION,
+}
 
-#[derive(Debug)]
+#[derive(Serialize)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PieceKind {
+    PAWN,
+    KNIGHT,
+    BISHOP,
+    ROOK,
+    QUEEN,
+    KING
+}
+
+impl PieceKind {
+    /// Reads a FEN piece letter, ignoring the case
+    pub fn from_char(symbol: char) -> Option<PieceKind> {
+        match symbol.to_ascii_lowercase() {
+            'p' => Some(PAWN),
+            'n' => Some(KNIGHT),
+            'b' => Some(BISHOP),
+            'r' => Some(ROOK),
+            'q' => Some(QUEEN),
+            'k' => Some(KING),
+            _ => None,
+        }
+    }
+
+    /// The uppercase FEN letter for this kind of piece
+    pub fn to_char(&self) -> char {
+        match self {
+            PAWN => 'P',
+            KNIGHT => 'N',
+            BISHOP => 'B',
+            ROOK => 'R',
+            QUEEN => 'Q',
+            KING => 'K',
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Piece {
+    pub kind: PieceKind,
+    pub color: Color,
+}
+
+impl Piece {
+    pub fn new(kind: PieceKind, color: Color) -> Piece {
+        Piece {
+            kind,
+            color
+        }
+    }
+
+    /// Reads a FEN piece letter, uppercase letters are white pieces and lowercase letters black pieces
+    pub fn from_char(symbol: char) -> Option<Piece> {
+        let kind = PieceKind::from_char(symbol)?;
+        let color = if symbol.is_ascii_uppercase() { WHITE } else { BLACK };
+
+        Some(Piece::new(kind, color))
+    }
+
+    /// The FEN letter for this piece
+    pub fn to_char(&self) -> char {
+        match self.color {
+            WHITE => self.kind.to_char(),
+            BLACK => self.kind.to_char().to_ascii_lowercase(),
+        }
+    }
+}
+
+pub type ChessBoard = Vec<Vec<Option<Piece>>>;
+
+#[derive(Debug, Clone)]
 pub struct Board {
     pub board: ChessBoard,
 }
@@ -37,1005 +133,3009 @@ impl Board {
         }
     }
 
-    pub fn get(&self, rank: usize, file: usize) -> char {
-        self.board[rank][file]
-    }
+    /// Creates a board from the piece placement field of a FEN string, checking that the board
+    /// is 8 by 8, only contains known pieces, has one king per side and no pawns on the first or last rank
+    pub fn from_fen(placement: &str) -> Result<Board, FenError> {
+        let board = Board {
+            board: parse_fen_placement(placement)?,
+        };
 
-    pub fn pushRow(&mut self, row: Vec<char>) {
-        self.board.push(row);
-    }
+        for color in [WHITE, BLACK] {
+            let king = Some(Piece::new(KING, color));
+            let count = board.board.iter().flatten().filter(|square| **square == king).count();
 
-    pub fn clone(&self) -> Self {
-        // Create a new Board instance
-        Board {
-            board: self.board.clone(), // Clone the 2D vector
+            if count != 1 {
+                return Err(FenError::WRONG_KING_COUNT(color, count));
+            }
         }
-    }
-
-    pub fn make_move(board: &Board, start: Position, end: &Position, piece: char) -> Board {
-        let mut new_board = board.clone();
-        new_board.board[start.rank][start.file] = '-';
-        new_board.board[end.rank][end.file] = piece;
-        new_board
-    }
-    /*
-    pub fn generate_legal_moves(&self, currentTurn: &Color) -> Vec<Board> {
-        let mut boards: Vec<Board> = Vec::new();
-
-        for (rowIndex, row) in self.board.iter().enumerate() {
-            for (fileIndex, piece) in row.iter().enumerate() {
-                if *currentTurn == WHITE {
-                    match piece {
-                        &'P' => {
-                            let legal_moves = get_pawn_moves(self, &Position::create(rowIndex, fileIndex));
-
-                            for legal_move in &legal_moves {
-                                let new_board = Self::make_move(self, Position::create(rowIndex, fileIndex), legal_move, 'P');
-                                boards.push(new_board);
-                            }
-                        }
-                        &'R' => {
-                            let legal_moves = get_rook_moves(self, &Position::create(rowIndex, fileIndex));
-                            for legal_move in legal_moves {
-                                let new_board = Self::make_move(self, Position::create(rowIndex, fileIndex), &legal_move, 'R');
-                                boards.push(new_board);
-                            }
-                        }
-                        &'N' => {
-                            let legal_moves = get_knight_moves(self, &Position::create(rowIndex, fileIndex));
-                            for legal_move in legal_moves {
-                                let new_board = Self::make_move(self, Position::create(rowIndex, fileIndex), &legal_move, 'N');
-                                boards.push(new_board);
-                            }
-                        }
-                        &'B' => {
-                            let legal_moves = get_bishop_moves(self, &Position::create(rowIndex, fileIndex));
-                            for legal_move in legal_moves {
-                                let new_board = Self::make_move(self, Position::create(rowIndex, fileIndex), &legal_move, 'B');
-                                boards.push(new_board);
-                            }
-                        }
-                        &'Q' => {
-                            let legal_moves = get_queen_moves(self, &Position::create(rowIndex, fileIndex));
-                            for legal_move in legal_moves {
-                                let new_board = Self::make_move(self, Position::create(rowIndex, fileIndex), &legal_move, 'Q');
-                                boards.push(new_board);
-                            }
-                        }
-                        &'K' => {
-                            let legal_moves = get_king_moves(self, &Position::create(rowIndex, fileIndex), WHITE, Game::new());
-                            for legal_move in legal_moves {
-                                let new_board = Self::make_move(self, Position::create(rowIndex, fileIndex), &legal_move, 'K');
-                                boards.push(new_board);
-                            }
-                        }
-                        _ => {}
-                    }
-                }
 
-                else {
-                    match piece {
-                        &'p' => {
-                            let legal_moves = get_pawn_moves(self, &Position::create(rowIndex, fileIndex));
-
-                            for legal_move in &legal_moves {
-                                let new_board = Self::make_move(self, Position::create(rowIndex, fileIndex), legal_move, 'p');
-                                boards.push(new_board);
-                            }
-                        }
-                        &'r' => {
-                            let legal_moves = get_rook_moves(self, &Position::create(rowIndex, fileIndex));
-                            for legal_move in legal_moves {
-                                let new_board = Self::make_move(self, Position::create(rowIndex, fileIndex), &legal_move, 'r');
-                                boards.push(new_board);
-                            }
-                        }
-                        &'n' => {
-                            let legal_moves = get_knight_moves(self, &Position::create(rowIndex, fileIndex));
-                            for legal_move in legal_moves {
-                                let new_board = Self::make_move(self, Position::create(rowIndex, fileIndex), &legal_move, 'n');
-                                boards.push(new_board);
-                            }
-                        }
-                        &'b' => {
-                            let legal_moves = get_bishop_moves(self, &Position::create(rowIndex, fileIndex));
-                            for legal_move in legal_moves {
-                                let new_board = Self::make_move(self, Position::create(rowIndex, fileIndex), &legal_move, 'b');
-                                boards.push(new_board);
-                            }
-                        }
-                        &'q' => {
-                            let legal_moves = get_queen_moves(self, &Position::create(rowIndex, fileIndex));
-                            for legal_move in legal_moves {
-                                let new_board = Self::make_move(self, Position::create(rowIndex, fileIndex), &legal_move, 'q');
-                                boards.push(new_board);
-                            }
-                        }
-                        &'k' => {
-                            let legal_moves = get_king_moves(self, &Position::create(rowIndex, fileIndex));
-                            for legal_move in legal_moves {
-                                let new_board = Self::make_move(self, Position::create(rowIndex, fileIndex), &legal_move, 'k');
-                                boards.push(new_board);
-                            }
-                        }
-                        _ => {}
-                    }
+        for rank in [0, 7] {
+            for file in 0..8 {
+                if matches!(board.get(rank, file), Some(Piece { kind: PAWN, .. })) {
+                    return Err(FenError::PAWN_ON_BACK_RANK(Position::create(rank, file).to_algebraic()));
                 }
             }
         }
 
-        boards
+        Ok(board)
     }
-     */
 
-    pub fn print(&self) {
-        for row in &self.board {
-            println!("{:?}", row);
-        }
+    pub fn get(&self, rank: usize, file: usize) -> Option<Piece> {
+        self.board[rank][file]
     }
 
-    /*
-    pub fn perft(&self, depth: usize, currentColor: Color) -> u64 {
-        if depth == 0 {
-            return 1;
-        }
-
-        let mut nodes = 0;
-
-        let moves = self.generate_legal_moves(&currentColor);
+    pub fn pushRow(&mut self, row: Vec<Option<Piece>>) {
+        self.board.push(row);
+    }
 
-        // Recursively count nodes at the next depth for each legal move
-        for new_board in moves {
-            let newColor = if currentColor == WHITE {
-                BLACK
-            }
-            else {
-                WHITE
-            };
+    pub fn make_move(board: &Board, start: Position, end: &Position, piece: Piece) -> Board {
+        let mut new_board = board.clone();
+        new_board.board[start.rank][start.file] = None;
+        new_board.board[end.rank][end.file] = Some(piece);
+        new_board
+    }
 
-            nodes += new_board.perft(depth - 1, newColor);
+    /// The piece placement field of a FEN string for this board
+    pub fn to_fen(&self) -> String {
+        convert_vector_to_fen(&self.board)
+    }
 
-            /*
-            new_board.print();
-            println!("");
-            println!("");
-             */
+    pub fn print(&self) {
+        for row in &self.board {
+            let symbols: Vec<char> = row.iter().map(|square| square.map_or('-', |piece| piece.to_char())).collect();
+            println!("{:?}", symbols);
         }
-
-        nodes
     }
-     */
 }
 
 #[derive(Serialize)]
 #[derive(Debug)]
-#[derive(Eq, Hash, PartialEq)]
+#[derive(Eq, Hash, PartialEq, Clone, Copy)]
 pub struct Position {
     rank: usize,
     file: usize
 }
 
 impl Position {
+    /// Creates a position from board indices, where rank 0 is the 8th rank and file 0 the a-file.
+    /// Panics when the rank or the file is 8 or more, use `new` to check untrusted values.
     pub fn create(rank: usize, file: usize) -> Position {
+        assert!(rank < 8 && file < 8, "Rank {} and file {} is outside the board", rank, file);
+
         Position {
             rank,
             file
         }
     }
+
+    /// Creates a position from board indices like `create`, but returns an error for squares outside the board
+    pub fn new(rank: usize, file: usize) -> Result<Position, ChessError> {
+        if rank > 7 || file > 7 {
+            return Err(ChessError::OUT_OF_BOUNDS(rank, file));
+        }
+
+        Ok(Position::create(rank, file))
+    }
+
+    /// Reads a square in algebraic notation like "e4"
+    pub fn from_algebraic(square: &str) -> Option<Position> {
+        let mut chars = square.chars();
+        let file = chars.next()?;
+        let rank = chars.next()?.to_digit(10)? as usize;
+
+        if chars.next().is_some() || !('a'..='h').contains(&file) || !(1..=8).contains(&rank) {
+            return None;
+        }
+
+        Some(Position::create(8 - rank, file as usize - 'a' as usize))
+    }
+
+    /// The square in algebraic notation like "e4"
+    pub fn to_algebraic(&self) -> String {
+        format!("{}{}", (b'a' + self.file as u8) as char, 8 - self.rank)
+    }
+
+    /// The board index of the rank, 0 is the 8th rank and 7 the 1st rank
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    /// The board index of the file, 0 is the a-file and 7 the h-file
+    pub fn file(&self) -> usize {
+        self.file
+    }
 }
 
-#[derive(PartialEq, Debug)]
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_algebraic())
+    }
+}
+
+#[derive(Serialize)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Color {
     WHITE,
     BLACK
 }
 
-pub enum Move {
-    REGULAR,
-    CAPTURE,
-    CASTLE
+impl Color {
+    pub fn opposite(&self) -> Color {
+        match self {
+            WHITE => BLACK,
+            BLACK => WHITE,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum CastleSide {
+    SHORT,
+    LONG
+}
+
+/// A single move with everything needed to show, log or replay it
+#[derive(Serialize)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Move {
+    pub from: Position,
+    pub to: Position,
+    pub piece: Piece,
+    pub captured: Option<Piece>,
+    pub promotion: Option<Piece>,
+    pub double_push: bool,
+    pub en_passant: bool,
+    pub castle: Option<CastleSide>,
+}
+
+impl Move {
+    pub fn is_capture(&self) -> bool {
+        self.captured.is_some()
+    }
+
+    pub fn is_promotion(&self) -> bool {
+        self.promotion.is_some()
+    }
+
+    pub fn is_castle(&self) -> bool {
+        self.castle.is_some()
+    }
 }
+
+#[derive(Clone)]
 pub struct Game {
     board: Board,
     status: Status,
-    current_move: Move,
+    current_move: Option<Move>,
     white_castle_short: bool,
     white_castle_long: bool,
     black_castle_short: bool,
     black_castle_long: bool,
-    en_passant_possible: bool,
+    // The square a pawn skipped over with a double step on the previous move
+    en_passant_possible: Option<Position>,
+    in_check: bool,
+    // Half moves since the last capture or pawn move
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    // Zobrist hash of the current position, see `Game::hash`
+    hash: u64,
+    // The hash of every position reached in the game, including the current one
+    position_history: Vec<u64>,
+    side_to_move: Color,
+    // Every move played so far, next to the state from before each move for undo
+    moves: Vec<Move>,
+    undo_states: Vec<UndoState>,
+    // Moves taken back with undo, the most recently undone one last
+    redo_moves: Vec<Move>,
+    // PGN tags like Event or White in the order they were set
+    tags: Vec<(String, String)>,
+}
+
+// The parts of a game that a move can't be reversed from, saved before the move is made
+#[derive(Clone, Copy)]
+struct UndoState {
+    status: Status,
+    current_move: Option<Move>,
+    white_castle_short: bool,
+    white_castle_long: bool,
+    black_castle_short: bool,
+    black_castle_long: bool,
+    en_passant_possible: Option<Position>,
+    in_check: bool,
+    halfmove_clock: u32,
+    hash: u64,
 }
 
 impl Game {
     pub fn new() -> Game {
-        Game {
+        let mut game = Game {
             status: WHITE_TO_MOVE,
-            current_move: REGULAR,
+            current_move: None,
             white_castle_short: true,
             white_castle_long: true,
             black_castle_short: true,
             black_castle_long: true,
-            en_passant_possible: false,
+            en_passant_possible: None,
+            in_check: false,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+            position_history: Vec::new(),
+            side_to_move: WHITE,
+            moves: Vec::new(),
+            undo_states: Vec::new(),
+            redo_moves: Vec::new(),
+            tags: Vec::new(),
             board: Board::create(),
-        }
-    }
-}
-
-pub fn convert_fen_to_vector(fen: &str) -> ChessBoard {
-    let mut board: ChessBoard = Vec::new();
-    let position = fen.split(" ").collect::<Vec<&str>>()[0];
+        };
 
-    for rank in position.split("/").collect::<Vec<&str>>() {
-        let mut row_vector = Vec::new();
+        game.hash = zobrist::hash(&game);
+        game.position_history.push(game.hash);
+        game
+    }
 
-        for square in rank.chars() {
-            match square.to_digit(10) {
-                Some(num) => {
-                    let num = num as i32;
+    /// Creates a game from all six FEN fields. The halfmove clock and fullmove number may be
+    /// left out, in which case they start at 0 and 1.
+    pub fn from_fen(fen: &str) -> Result<Game, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
 
-                    for _i in 0..num {
-                        row_vector.push('-');
-                    }
-                }
+        if fields.len() < 4 {
+            return Err(FenError::MISSING_FIELDS);
+        }
 
-                None => row_vector.push(square)
-            }
+        if fields.len() > 6 {
+            return Err(FenError::TOO_MANY_FIELDS(fields.len()));
         }
 
-        board.push(row_vector);
-    }
+        let board = Board::from_fen(fields[0])?;
 
-    board
-}
+        let status = match fields[1] {
+            "w" => WHITE_TO_MOVE,
+            "b" => BLACK_TO_MOVE,
+            other => return Err(FenError::INVALID_SIDE_TO_MOVE(other.to_string())),
+        };
 
-fn get_piece_from_position(board: &Board, piece_pos: &Position) -> char {
-    board.get(piece_pos.rank, piece_pos.file)
-}
+        let castling = fields[2];
 
-fn get_castling_position(game: &Game) -> Vec<Position> {
-    let mut positons = Vec::new();
+        // The rights are written in the order KQkq, each one at most once
+        let mut order = "KQkq".chars();
+        let in_order = castling.chars().all(|right| order.any(|expected| expected == right));
 
-    if game.status == WHITE_TO_MOVE {
-        if game.white_castle_short {
-            if game.board.get(7, 5) == '-' && game.board.get(7, 6) == '-' {
-                positons.push(Position {
-                    rank: 7,
-                    file: 6
-                })
-            }
+        if castling.is_empty() || (castling != "-" && (!in_order || !castling.chars().all(|right| is_possible_castling_right(&board, right)))) {
+            return Err(FenError::INVALID_CASTLING(castling.to_string()));
         }
 
-        if game.white_castle_short {
-            if game.board.get(7, 1) == '-' && game.board.get(7, 2) == '-' && game.board.get(7, 3) == '-' {
-                positons.push(Position {
-                    rank: 7,
-                    file: 2
-                })
-            }
-        }
-    }
+        let en_passant_possible = match fields[3] {
+            "-" => None,
+            square => match Position::from_algebraic(square) {
+                Some(position) if is_possible_en_passant(&board, &position, status == WHITE_TO_MOVE) => Some(position),
+                _ => return Err(FenError::INVALID_EN_PASSANT(square.to_string())),
+            },
+        };
 
-    else {
-        if game.black_castle_short {
-            if game.board.get(0, 5) == '-' && game.board.get(0, 6) == '-' {
-                positons.push(Position {
-                    rank: 0,
-                    file: 6
-                })
-            }
+        let halfmove_clock = match fields.get(4) {
+            Some(field) => parse_clock(field).ok_or_else(|| FenError::INVALID_HALFMOVE_CLOCK(field.to_string()))?,
+            None => 0,
+        };
+
+        // The pawn that skipped the en passant square moved on the last half move
+        if en_passant_possible.is_some() && halfmove_clock != 0 {
+            return Err(FenError::INVALID_EN_PASSANT(fields[3].to_string()));
         }
 
-        if game.black_castle_long {
-            if game.board.get(0, 1) == '-' && game.board.get(0, 2) == '-' && game.board.get(0, 3) == '-' {
-                positons.push(Position {
-                    rank: 0,
-                    file: 2
-                })
+        let fullmove_number = match fields.get(5) {
+            Some(field) => match parse_clock(field) {
+                Some(number) if number > 0 => number,
+                _ => return Err(FenError::INVALID_FULLMOVE_NUMBER(field.to_string())),
+            },
+            None => 1,
+        };
+
+        let mut game = Game {
+            board,
+            status,
+            current_move: None,
+            white_castle_short: castling.contains('K'),
+            white_castle_long: castling.contains('Q'),
+            black_castle_short: castling.contains('k'),
+            black_castle_long: castling.contains('q'),
+            en_passant_possible,
+            in_check: false,
+            halfmove_clock,
+            fullmove_number,
+            hash: 0,
+            position_history: Vec::new(),
+            side_to_move: if fields[1] == "w" { WHITE } else { BLACK },
+            moves: Vec::new(),
+            undo_states: Vec::new(),
+            redo_moves: Vec::new(),
+            tags: Vec::new(),
+        };
+
+        let opponent = game.side_to_move.opposite();
+
+        if let Some(king_pos) = find_king(&game.board, opponent) {
+            if is_square_attacked(&game.board, &king_pos, game.side_to_move) {
+                return Err(FenError::OPPONENT_IN_CHECK);
             }
         }
+
+        game.hash = zobrist::hash(&game);
+        game.position_history.push(game.hash);
+
+        update_status(&mut game);
+
+        Ok(game)
     }
 
-    positons
-}
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
 
-fn has_enemy_piece(board: &Board, pos: &Position, current_piece: char) -> bool {
-    let piece = get_piece_from_position(&board, &pos);
+    /// The board of the current position, for reading squares or checking attacks
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
 
-    if current_piece.is_uppercase() && piece.is_lowercase() {
-        return true;
+    /// Whether the side to move is currently in check
+    pub fn in_check(&self) -> bool {
+        self.in_check
     }
 
-    if current_piece.is_lowercase() && piece.is_uppercase() {
-        return true;
+    /// The last move that was played, if any
+    pub fn current_move(&self) -> Option<&Move> {
+        self.current_move.as_ref()
     }
 
-    false
-}
+    /// A 64-bit Zobrist hash of the position, covering the pieces, the side to move, the castling
+    /// rights and the en passant file when a capture is actually possible. The same position always
+    /// has the same hash, also across runs, so hashes can be stored.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
 
-fn get_pawn_capture_pos(board: &Board, pawn_pos: &Position, pawn: char) -> Vec<Position> {
-    let mut target: Vec<Position> = Vec::new();
-    let mut target_rank = 0;
+    /// The moves played so far in order, without the ones taken back with `undo`
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
 
-    if pawn.is_lowercase() {
-        target_rank = pawn_pos.rank + 1;
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
     }
 
-    else {
-        target_rank = pawn_pos.rank - 1;
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
     }
 
-    if (pawn_pos.file > 0) {
-        let square2 = pawn_pos.file - 1;
-        let target2 = Position::create(target_rank, square2);
-        let has_enemy2 = has_enemy_piece(&board, &target2, pawn);
+    /// Returns the reason a draw can be claimed in the current position, if any. It is the
+    /// reason attached to `Status::WHITE_CAN_CLAIM_DRAW` or `Status::BLACK_CAN_CLAIM_DRAW`.
+    /// Draws that are not claimed by a player are declared automatically by `make_move` instead.
+    pub fn claimable_draw(&self) -> Option<DrawReason> {
+        match self.status {
+            WHITE_CAN_CLAIM_DRAW(reason) | BLACK_CAN_CLAIM_DRAW(reason) => Some(reason),
+            _ => None,
+        }
+    }
 
-        if pawn_pos.file == 7 && has_enemy2 {
-            target.push(Position::create(target_rank, square2));
+    /// How many times the current position has occurred in the game, counting the current occurrence
+    pub fn repetition_count(&self) -> usize {
+        match self.position_history.last() {
+            Some(current) => self.position_history.iter().filter(|key| *key == current).count(),
+            None => 0,
         }
+    }
 
-        else if has_enemy2 {
-            target.push(Position::create(target_rank, square2))
+    /// Ends the game as a draw if the side to move is entitled to claim one
+    pub fn claim_draw(&mut self) -> Result<bool, ChessError> {
+        match self.claimable_draw() {
+            Some(reason) => {
+                self.status = DRAW(reason);
+                Ok(true)
+            }
+            None => Err(ChessError::NO_DRAW_TO_CLAIM),
         }
     }
 
-    if (pawn_pos.file < 7) {
-        let square1 = pawn_pos.file + 1;
-        let target1 = Position::create(target_rank, square1);
-        let has_enemy1 = has_enemy_piece(&board, &target1, pawn);
+    /// Takes back the last move and restores the game exactly as it was before it, including
+    /// castling rights, en passant, clocks and status. Returns the move, or None at the start of the game.
+    pub fn undo(&mut self) -> Option<Move> {
+        let chess_move = self.unmake_move()?;
+        self.redo_moves.push(chess_move);
 
-        if pawn_pos.file == 0 && has_enemy1 {
-            target.push(Position::create(target_rank, square1));
+        Some(chess_move)
+    }
+
+    // Takes back the last move without remembering it for redo
+    fn unmake_move(&mut self) -> Option<Move> {
+        let chess_move = self.moves.pop()?;
+        let state = self.undo_states.pop()?;
+        let start = &chess_move.from;
+        let end = &chess_move.to;
+
+        self.board.board[start.rank][start.file] = Some(chess_move.piece);
+        self.board.board[end.rank][end.file] = if chess_move.en_passant { None } else { chess_move.captured };
+
+        if chess_move.en_passant {
+            self.board.board[start.rank][end.file] = chess_move.captured;
         }
 
-        else if has_enemy1 {
-            target.push(Position::create(target_rank, square1));
+        if chess_move.is_castle() {
+            let (rook_start, rook_end) = if chess_move.castle == Some(SHORT) { (7, 5) } else { (0, 3) };
+
+            self.board.board[end.rank][rook_start] = self.board.board[end.rank][rook_end];
+            self.board.board[end.rank][rook_end] = None;
+        }
+
+        if chess_move.piece.color == BLACK {
+            self.fullmove_number -= 1;
         }
+
+        self.status = state.status;
+        self.current_move = state.current_move;
+        self.white_castle_short = state.white_castle_short;
+        self.white_castle_long = state.white_castle_long;
+        self.black_castle_short = state.black_castle_short;
+        self.black_castle_long = state.black_castle_long;
+        self.en_passant_possible = state.en_passant_possible;
+        self.in_check = state.in_check;
+        self.halfmove_clock = state.halfmove_clock;
+        self.hash = state.hash;
+        self.side_to_move = chess_move.piece.color;
+        self.position_history.pop();
+
+        Some(chess_move)
     }
 
-    target
-}
+    /// Plays the last move taken back with `undo` again. Making any other move clears the moves that can be redone.
+    pub fn redo(&mut self) -> Option<Move> {
+        let chess_move = *self.redo_moves.last()?;
 
-fn get_horizontal_moves(board: &Board, piece_pos: &Position) -> Vec<Position> {
-    let mut positions: Vec<Position> = Vec::new();
-    let rank = piece_pos.rank;
-    let mut file = piece_pos.file;
+        play_move(self, &chess_move).ok()?;
+        self.redo_moves.pop();
 
-    loop {
-        if file == 7 {
-            if rank != piece_pos.rank || file != piece_pos.file {
-                positions.push(Position::create(rank, 7));
-            }
+        Some(chess_move)
+    }
 
-            file = piece_pos.file;
+    /// Counts the positions reached after exactly `depth` moves from the current position. Draw
+    /// rules are ignored, only the legal moves count, so the numbers can be compared with other engines.
+    pub fn perft(&mut self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
 
-            break;
+        let moves = generate_moves(self);
+
+        if depth == 1 {
+            return moves.len() as u64;
         }
 
-        if rank == piece_pos.rank && file == piece_pos.file {
-            file += 1;
-            continue;
+        let mut nodes = 0;
+
+        for chess_move in moves {
+            apply_move(self, chess_move);
+            nodes += self.perft(depth - 1);
+            self.unmake_move();
         }
 
-        if board.get(rank, file) != '-'  {
-            if is_enemy(board, piece_pos, &Position::create(rank, file)) {
-                positions.push(Position::create(rank, file));
-            }
+        nodes
+    }
 
-            file = piece_pos.file;
+    /// Runs perft for every legal move and prints the node count per move, followed by the total.
+    /// Comparing the counts with another engine shows which move leads to a move generation bug.
+    pub fn perft_divide(&mut self, depth: usize) -> Vec<(Move, u64)> {
+        let mut counts: Vec<(Move, u64)> = Vec::new();
 
-            break;
+        for chess_move in generate_moves(self) {
+            apply_move(self, chess_move);
+            let nodes = if depth > 1 { self.perft(depth - 1) } else { 1 };
+            self.unmake_move();
+
+            println!("{}: {}", chess_move.to_uci(), nodes);
+            counts.push((chess_move, nodes));
         }
 
-        positions.push(Position::create(rank, file));
+        println!();
+        println!("Nodes searched: {}", counts.iter().map(|(_, nodes)| nodes).sum::<u64>());
 
-        file += 1;
+        counts
     }
 
-    loop {
-        if file == 0 {
-            if rank != piece_pos.rank || file != piece_pos.file {
-                positions.push(Position::create(rank, 0));
-            }
+    pub fn is_game_over(&self) -> bool {
+        !matches!(self.status, WHITE_TO_MOVE | BLACK_TO_MOVE | WHITE_CAN_CLAIM_DRAW(_) | BLACK_CAN_CLAIM_DRAW(_))
+    }
 
-            file = piece_pos.file;
+    /// The color whose turn it is, this is also known after the game has ended
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
 
-            break;
-        }
+    /// Writes the game as a six-field FEN string that `Game::from_fen` reads back to the same game
+    pub fn to_fen(&self) -> String {
+        let side_to_move = if self.side_to_move == WHITE { "w" } else { "b" };
 
-        if rank == piece_pos.rank && file == piece_pos.file {
-            file -= 1;
-            continue;
-        }
+        let mut castling = String::new();
 
-        if board.get(rank, file) != '-' {
-            if is_enemy(board, piece_pos, &Position::create(rank, file)) {
-                positions.push(Position::create(rank, file));
+        for (right, symbol) in [(self.white_castle_short, 'K'), (self.white_castle_long, 'Q'), (self.black_castle_short, 'k'), (self.black_castle_long, 'q')] {
+            if right {
+                castling.push(symbol);
             }
+        }
 
-            file = piece_pos.file;
-
-            break;
+        if castling.is_empty() {
+            castling.push('-');
         }
 
-        positions.push(Position::create(rank, file));
+        let en_passant = match self.en_passant_possible {
+            Some(position) => position.to_algebraic(),
+            None => "-".to_string(),
+        };
 
-        file -= 1;
+        format!("{} {} {} {} {} {}", self.board.to_fen(), side_to_move, castling, en_passant, self.halfmove_clock, self.fullmove_number)
     }
+}
 
-    positions
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
 }
 
-fn is_enemy(board: &Board, current_pos: &Position, target_pos: &Position) -> bool {
-    let current_piece = board.get(current_pos.rank, current_pos.file);
+#[derive(PartialEq, Debug)]
+pub enum ChessError {
+    // The color whose turn it is
+    WRONG_TURN(Color),
+    NO_PIECE_ON_SQUARE(Position),
+    ILLEGAL_MOVE,
+    GAME_OVER,
+    PROMOTION_REQUIRED,
+    INVALID_PROMOTION(PieceKind),
+    // The rank and file indices that were given
+    OUT_OF_BOUNDS(usize, usize),
+    NO_DRAW_TO_CLAIM,
+    INVALID_UCI(String),
+    // The id of a node that is not in the game tree
+    UNKNOWN_NODE(usize),
+}
 
-    if current_piece.is_lowercase() {
-        if board.get(target_pos.rank, target_pos.file).is_uppercase() {
-            return true;
+impl std::fmt::Display for ChessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ChessError::WRONG_TURN(WHITE) => write!(f, "Error: Cannot move black piece on white turn"),
+            ChessError::WRONG_TURN(BLACK) => write!(f, "Error: Cannot move white piece on black turn"),
+            ChessError::NO_PIECE_ON_SQUARE(position) => write!(f, "Error: There is no piece on {}", position),
+            ChessError::ILLEGAL_MOVE => write!(f, "Error: Move is invalid"),
+            ChessError::GAME_OVER => write!(f, "Error: The game is already over"),
+            ChessError::PROMOTION_REQUIRED => write!(f, "Error: A promotion piece is required"),
+            ChessError::INVALID_PROMOTION(kind) => write!(f, "Error: Cannot promote to {:?}", kind),
+            ChessError::OUT_OF_BOUNDS(rank, file) => write!(f, "Error: Rank {} and file {} is outside the board", rank, file),
+            ChessError::NO_DRAW_TO_CLAIM => write!(f, "Error: No draw can be claimed in this position"),
+            ChessError::INVALID_UCI(uci) => write!(f, "Error: {} is not a move in UCI notation", uci),
+            ChessError::UNKNOWN_NODE(id) => write!(f, "Error: There is no node {} in the game tree", id),
         }
     }
+}
 
-    else {
-        if board.get(target_pos.rank, target_pos.file).is_lowercase() {
-            return true;
+impl std::error::Error for ChessError {}
+
+#[derive(PartialEq, Debug)]
+pub enum FenError {
+    MISSING_FIELDS,
+    // The number of fields that were given
+    TOO_MANY_FIELDS(usize),
+    WRONG_RANK_COUNT(usize),
+    // The rank as written on the board (8 to 1) and the number of squares it describes
+    WRONG_RANK_WIDTH(usize, usize),
+    // The rank as written on the board, two digits in a row should have been added up
+    CONSECUTIVE_DIGITS(usize),
+    INVALID_CHARACTER(char),
+    WRONG_KING_COUNT(Color, usize),
+    PAWN_ON_BACK_RANK(String),
+    OPPONENT_IN_CHECK,
+    INVALID_SIDE_TO_MOVE(String),
+    INVALID_CASTLING(String),
+    INVALID_EN_PASSANT(String),
+    INVALID_HALFMOVE_CLOCK(String),
+    INVALID_FULLMOVE_NUMBER(String),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FenError::MISSING_FIELDS => write!(f, "Error: FEN needs at least the placement, side to move, castling and en passant fields"),
+            FenError::TOO_MANY_FIELDS(count) => write!(f, "Error: FEN has {} fields instead of at most 6", count),
+            FenError::WRONG_RANK_COUNT(count) => write!(f, "Error: The board has {} ranks instead of 8", count),
+            FenError::WRONG_RANK_WIDTH(rank, width) => write!(f, "Error: Rank {} has {} squares instead of 8", rank, width),
+            FenError::CONSECUTIVE_DIGITS(rank) => write!(f, "Error: Rank {} has two digits in a row", rank),
+            FenError::INVALID_CHARACTER(character) => write!(f, "Error: Invalid character {} in piece placement", character),
+            FenError::WRONG_KING_COUNT(color, count) => write!(f, "Error: {:?} has {} kings instead of 1", color, count),
+            FenError::PAWN_ON_BACK_RANK(square) => write!(f, "Error: Pawn on the first or last rank at {}", square),
+            FenError::OPPONENT_IN_CHECK => write!(f, "Error: The side that is not to move is in check"),
+            FenError::INVALID_SIDE_TO_MOVE(field) => write!(f, "Error: Invalid side to move {}", field),
+            FenError::INVALID_CASTLING(field) => write!(f, "Error: Invalid castling rights {}", field),
+            FenError::INVALID_EN_PASSANT(field) => write!(f, "Error: Invalid en passant square {}", field),
+            FenError::INVALID_HALFMOVE_CLOCK(field) => write!(f, "Error: Invalid halfmove clock {}", field),
+            FenError::INVALID_FULLMOVE_NUMBER(field) => write!(f, "Error: Invalid fullmove number {}", field),
         }
     }
-
-    false
 }
 
-fn get_vertical_moves(board: &Board, piece_pos: &Position) -> Vec<Position> {
-    let mut positions: Vec<Position> = Vec::new();
-    let mut rank = piece_pos.rank;
-    let file = piece_pos.file;
+impl std::error::Error for FenError {}
+
+// A castling right needs the king and the rook on their starting squares
+fn is_possible_castling_right(board: &Board, right: char) -> bool {
+    let (king, rook, rook_file) = match right {
+        'K' => ('K', 'R', 7),
+        'Q' => ('K', 'R', 0),
+        'k' => ('k', 'r', 7),
+        'q' => ('k', 'r', 0),
+        _ => return false,
+    };
+
+    let rank = if king == 'K' { 7 } else { 0 };
+
+    board.get(rank, 4) == Piece::from_char(king) && board.get(rank, rook_file) == Piece::from_char(rook)
+}
+
+#[derive(PartialEq, Debug)]
+pub enum SanError {
+    // The text is not a move in algebraic notation
+    MALFORMED(String),
+    // No legal move in the position fits the text
+    ILLEGAL_MOVE(String),
+    // More than one legal move fits the text
+    AMBIGUOUS_MOVE(String),
+}
+
+impl std::fmt::Display for SanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SanError::MALFORMED(san) => write!(f, "Error: {} is not a move in algebraic notation", san),
+            SanError::ILLEGAL_MOVE(san) => write!(f, "Error: {} is not a legal move", san),
+            SanError::AMBIGUOUS_MOVE(san) => write!(f, "Error: {} fits more than one legal move, add the file or rank of the piece", san),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+/// An error in a PGN file, with the line and column where it was found, both counted from 1
+#[derive(PartialEq, Debug)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: PgnErrorKind,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum PgnErrorKind {
+    INVALID_TAG,
+    INVALID_FEN(FenError),
+    UNTERMINATED_COMMENT,
+    UNTERMINATED_VARIATION,
+    UNEXPECTED_CHARACTER(char),
+    // A variation, a closing parenthesis or an annotation where it doesn't belong
+    UNEXPECTED_TOKEN(String),
+    ILLEGAL_MOVE(SanError),
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Error: Line {}, column {}: ", self.line, self.column)?;
+
+        match &self.kind {
+            PgnErrorKind::INVALID_TAG => write!(f, "Tags must look like [Name \"Value\"]"),
+            PgnErrorKind::INVALID_FEN(error) => write!(f, "Invalid FEN tag, {}", error.to_string().trim_start_matches("Error: ")),
+            PgnErrorKind::UNTERMINATED_COMMENT => write!(f, "The comment is never closed"),
+            PgnErrorKind::UNTERMINATED_VARIATION => write!(f, "The variation is never closed"),
+            PgnErrorKind::UNEXPECTED_CHARACTER(character) => write!(f, "Unexpected character {}", character),
+            PgnErrorKind::UNEXPECTED_TOKEN(token) => write!(f, "Unexpected {}", token),
+            PgnErrorKind::ILLEGAL_MOVE(error) => write!(f, "{}", error.to_string().trim_start_matches("Error: ")),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+pub fn convert_fen_to_vector(fen: &str) -> ChessBoard {
+    let mut board: ChessBoard = Vec::new();
+    let position = fen.split(" ").collect::<Vec<&str>>()[0];
+
+    for rank in position.split("/").collect::<Vec<&str>>() {
+        let mut row_vector = Vec::new();
+
+        for square in rank.chars() {
+            match square.to_digit(10) {
+                Some(num) => {
+                    for _i in 0..num {
+                        row_vector.push(None);
+                    }
+                }
+
+                None => row_vector.push(Piece::from_char(square))
+            }
+        }
+
+        board.push(row_vector);
+    }
+
+    board
+}
+
+/// Like `convert_fen_to_vector`, but rejects placements that do not describe an 8 by 8 board of known pieces
+pub fn parse_fen_placement(placement: &str) -> Result<ChessBoard, FenError> {
+    let ranks: Vec<&str> = placement.split('/').collect();
+
+    if ranks.len() != 8 {
+        return Err(FenError::WRONG_RANK_COUNT(ranks.len()));
+    }
+
+    let mut board: ChessBoard = Vec::new();
+
+    for (index, rank) in ranks.iter().enumerate() {
+        let mut row_vector = Vec::new();
+        let mut previous_digit = false;
+
+        for square in rank.chars() {
+            if previous_digit && square.is_ascii_digit() {
+                return Err(FenError::CONSECUTIVE_DIGITS(8 - index));
+            }
+
+            previous_digit = square.is_ascii_digit();
+
+            match square {
+                '1'..='8' => {
+                    for _i in 0..square.to_digit(10).unwrap() {
+                        row_vector.push(None);
+                    }
+                }
+                _ => match Piece::from_char(square) {
+                    Some(piece) => row_vector.push(Some(piece)),
+                    None => return Err(FenError::INVALID_CHARACTER(square)),
+                },
+            }
+        }
+
+        if row_vector.len() != 8 {
+            return Err(FenError::WRONG_RANK_WIDTH(8 - index, row_vector.len()));
+        }
+
+        board.push(row_vector);
+    }
+
+    Ok(board)
+}
+
+// The clocks are plain decimal numbers, without a sign
+fn parse_clock(field: &str) -> Option<u32> {
+    if field.is_empty() || !field.chars().all(|digit| digit.is_ascii_digit()) {
+        return None;
+    }
+
+    field.parse().ok()
+}
+
+// The en passant square has to be the empty square right behind a pawn of the side that just
+// moved, on the rank that pawn skipped over, with the square it came from empty as well
+fn is_possible_en_passant(board: &Board, target: &Position, white_to_move: bool) -> bool {
+    let (target_rank, start_rank, pawn_rank, pawn_color) = if white_to_move { (2, 1, 3, BLACK) } else { (5, 6, 4, WHITE) };
+
+    target.rank == target_rank
+        && board.get(target.rank, target.file).is_none()
+        && board.get(start_rank, target.file).is_none()
+        && board.get(pawn_rank, target.file) == Some(Piece::new(PAWN, pawn_color))
+}
+
+/// The reverse of `convert_fen_to_vector`, runs of empty squares are written as digits
+pub fn convert_vector_to_fen(board: &ChessBoard) -> String {
+    let mut ranks: Vec<String> = Vec::new();
+
+    for row in board {
+        let mut rank = String::new();
+        let mut empty = 0;
+
+        for square in row {
+            let piece = match square {
+                Some(piece) => piece,
+                None => {
+                    empty += 1;
+                    continue;
+                }
+            };
+
+            if empty > 0 {
+                rank.push_str(&empty.to_string());
+                empty = 0;
+            }
+
+            rank.push(piece.to_char());
+        }
+
+        if empty > 0 {
+            rank.push_str(&empty.to_string());
+        }
+
+        ranks.push(rank);
+    }
+
+    ranks.join("/")
+}
+
+fn get_piece_from_position(board: &Board, piece_pos: &Position) -> Option<Piece> {
+    board.get(piece_pos.rank, piece_pos.file)
+}
+
+fn get_castling_position(game: &Game, color: Color) -> Vec<Position> {
+    let mut positons = Vec::new();
+
+    let (rank, castle_short, castle_long) = if color == WHITE {
+        (7, game.white_castle_short, game.white_castle_long)
+    } else {
+        (0, game.black_castle_short, game.black_castle_long)
+    };
+
+    let king = Some(Piece::new(KING, color));
+    let rook = Some(Piece::new(ROOK, color));
+
+    // The king may not castle out of or through check, landing in check is filtered out by get_moves
+    let is_safe = |file: usize| !is_square_attacked(&game.board, &Position::create(rank, file), color.opposite());
+
+    if game.board.get(rank, 4) != king || !is_safe(4) {
+        return positons;
+    }
+
+    if castle_short && game.board.get(rank, 7) == rook && game.board.get(rank, 5).is_none() && game.board.get(rank, 6).is_none() && is_safe(5) {
+        positons.push(Position {
+            rank,
+            file: 6
+        })
+    }
+
+    if castle_long && game.board.get(rank, 0) == rook && game.board.get(rank, 1).is_none() && game.board.get(rank, 2).is_none() && game.board.get(rank, 3).is_none() && is_safe(3) {
+        positons.push(Position {
+            rank,
+            file: 2
+        })
+    }
+
+    positons
+}
+
+fn has_enemy_piece(board: &Board, pos: &Position, current_color: Color) -> bool {
+    match get_piece_from_position(board, pos) {
+        Some(piece) => piece.color != current_color,
+        None => false,
+    }
+}
+
+fn get_pawn_capture_pos(board: &Board, pawn_pos: &Position, pawn: Color) -> Vec<Position> {
+    let mut target: Vec<Position> = Vec::new();
+
+    let target_rank = if pawn == BLACK {
+        pawn_pos.rank + 1
+    } else {
+        pawn_pos.rank - 1
+    };
+
+    if pawn_pos.file > 0 {
+        let square2 = pawn_pos.file - 1;
+        let target2 = Position::create(target_rank, square2);
+        let has_enemy2 = has_enemy_piece(board, &target2, pawn);
+
+        if has_enemy2 {
+            target.push(Position::create(target_rank, square2));
+        }
+    }
+
+    if pawn_pos.file < 7 {
+        let square1 = pawn_pos.file + 1;
+        let target1 = Position::create(target_rank, square1);
+        let has_enemy1 = has_enemy_piece(board, &target1, pawn);
+
+        if has_enemy1 {
+            target.push(Position::create(target_rank, square1));
+        }
+    }
+
+    target
+}
+
+fn get_horizontal_moves(board: &Board, piece_pos: &Position) -> Vec<Position> {
+    let mut positions: Vec<Position> = Vec::new();
+    let rank = piece_pos.rank;
+    let mut file = piece_pos.file;
 
     loop {
-        if rank == 7 {
-            if file != piece_pos.file || rank != piece_pos.rank {
-                positions.push(Position::create(7, file));
+        if file == 7 {
+            if (rank != piece_pos.rank || file != piece_pos.file) && is_free_or_enemy(board, piece_pos, &Position::create(rank, 7)) {
+                positions.push(Position::create(rank, 7));
             }
 
-            rank = piece_pos.rank;
+            file = piece_pos.file;
 
             break;
         }
 
         if rank == piece_pos.rank && file == piece_pos.file {
-            rank += 1;
+            file += 1;
             continue;
         }
 
-        if board.get(rank, file) != '-' {
+        if board.get(rank, file).is_some() {
             if is_enemy(board, piece_pos, &Position::create(rank, file)) {
                 positions.push(Position::create(rank, file));
             }
 
-            rank = piece_pos.rank;
+            file = piece_pos.file;
 
             break;
         }
 
         positions.push(Position::create(rank, file));
 
-        rank += 1;
+        file += 1;
     }
 
     loop {
-        if rank == 0 {
-            if file != piece_pos.file || rank != piece_pos.rank {
-                positions.push(Position::create(rank, file));
+        if file == 0 {
+            if (rank != piece_pos.rank || file != piece_pos.file) && is_free_or_enemy(board, piece_pos, &Position::create(rank, 0)) {
+                positions.push(Position::create(rank, 0));
             }
 
-            rank = piece_pos.rank;
-
             break;
         }
 
         if rank == piece_pos.rank && file == piece_pos.file {
-            rank -= 1;
+            file -= 1;
             continue;
         }
 
-        if board.get(rank, file) != '-' {
+        if board.get(rank, file).is_some() {
             if is_enemy(board, piece_pos, &Position::create(rank, file)) {
                 positions.push(Position::create(rank, file));
             }
 
-            rank = piece_pos.rank;
-
             break;
         }
 
         positions.push(Position::create(rank, file));
 
-        rank -= 1;
+        file -= 1;
     }
 
     positions
 }
 
-fn get_diagonal_moves(board: &Board, piece_pos: &Position) -> Vec<Position> {
-    let mut positions: Vec<Position> = Vec::new();
-    let mut rank = piece_pos.rank;
-    let mut file = piece_pos.file;
+fn is_enemy(board: &Board, current_pos: &Position, target_pos: &Position) -> bool {
+    match (get_piece_from_position(board, current_pos), get_piece_from_position(board, target_pos)) {
+        (Some(current_piece), Some(target_piece)) => current_piece.color != target_piece.color,
+        _ => false,
+    }
+}
 
-    if rank > 0 && file > 0 {
-        loop {
-            if board.get(rank - 1, file - 1) != '-' {
-                if is_enemy(board, piece_pos, &Position::create(rank - 1, file - 1)) {
-                    positions.push(Position::create(rank - 1, file - 1));
-                }
+fn is_free_or_enemy(board: &Board, current_pos: &Position, target_pos: &Position) -> bool {
+    board.get(target_pos.rank, target_pos.file).is_none() || is_enemy(board, current_pos, target_pos)
+}
 
-                rank = piece_pos.rank;
-                file = piece_pos.file;
-                break;
+fn find_king(board: &Board, color: Color) -> Option<Position> {
+    let king = Some(Piece::new(KING, color));
+
+    for (rank, row) in board.board.iter().enumerate() {
+        for (file, square) in row.iter().enumerate() {
+            if *square == king {
+                return Some(Position::create(rank, file));
             }
+        }
+    }
 
-            positions.push(Position::create(rank - 1, file - 1));
+    None
+}
 
-            rank = rank - 1;
-            file = file - 1;
+/// Returns the positions of all pieces of the given color that attack the square.
+/// The square does not need to be occupied, so this also works for squares a king wants to move to.
+pub fn attackers_of(board: &Board, target: &Position, attacker: Color) -> Vec<Position> {
+    let mut attackers: Vec<Position> = Vec::new();
+
+    // Looks outwards from the target square using the same move helpers as the pieces themselves.
+    // A piece of the defending color is placed on the target so that the helpers stop on, and
+    // include, the first piece of the attacking color in every direction.
+    let is_attacker = |board: &Board, pos: &Position, kinds: &[PieceKind]| match board.get(pos.rank, pos.file) {
+        Some(piece) => piece.color == attacker && kinds.contains(&piece.kind),
+        None => false,
+    };
+
+    let mut probe = board.clone();
+    probe.board[target.rank][target.file] = Some(Piece::new(KING, attacker.opposite()));
+
+    for pos in get_diagonal_moves(&probe, target) {
+        if is_attacker(&probe, &pos, &[BISHOP, QUEEN]) {
+            attackers.push(pos);
+        }
+    }
 
-            if rank == 0 || file == 0 {
-                rank = piece_pos.rank;
-                file = piece_pos.file;
-                break;
-            }
+    for pos in get_horizontal_moves(&probe, target).into_iter().chain(get_vertical_moves(&probe, target)) {
+        if is_attacker(&probe, &pos, &[ROOK, QUEEN]) {
+            attackers.push(pos);
         }
     }
 
-    if rank > 0 && file < 7 {
-        loop {
-            if board.get(rank - 1, file + 1) != '-' {
-                if is_enemy(board, piece_pos, &Position::create(rank - 1, file + 1)) {
-                    positions.push(Position::create(rank - 1, file + 1));
-                }
+    probe.board[target.rank][target.file] = Some(Piece::new(KNIGHT, attacker.opposite()));
 
-                rank = piece_pos.rank;
-                file = piece_pos.file;
-                break;
+    for pos in get_knight_moves(&probe, target) {
+        if is_attacker(&probe, &pos, &[KNIGHT]) {
+            attackers.push(pos);
+        }
+    }
+
+    for rank in target.rank.saturating_sub(1)..=(target.rank + 1).min(7) {
+        for file in target.file.saturating_sub(1)..=(target.file + 1).min(7) {
+            let pos = Position::create(rank, file);
+
+            if pos != *target && is_attacker(board, &pos, &[KING]) {
+                attackers.push(pos);
             }
+        }
+    }
 
-            positions.push(Position::create(rank - 1, file + 1));
+    // White pawns attack towards rank 0 and black pawns towards rank 7
+    let pawn_rank = if attacker == WHITE { target.rank + 1 } else { target.rank.wrapping_sub(1) };
 
-            rank = rank - 1;
-            file = file + 1;
+    if pawn_rank <= 7 {
+        if target.file > 0 && is_attacker(board, &Position::create(pawn_rank, target.file - 1), &[PAWN]) {
+            attackers.push(Position::create(pawn_rank, target.file - 1));
+        }
 
-            if rank == 0 || file == 7 {
-                rank = piece_pos.rank;
-                file = piece_pos.file;
-                break;
-            }
+        if target.file < 7 && is_attacker(board, &Position::create(pawn_rank, target.file + 1), &[PAWN]) {
+            attackers.push(Position::create(pawn_rank, target.file + 1));
         }
     }
 
-    if rank < 7 && file > 0 {
-        loop {
-            if board.get(rank + 1, file - 1) != '-' {
-                if is_enemy(board, piece_pos, &Position::create(rank + 1, file - 1)) {
-                    positions.push(Position::create(rank + 1, file - 1));
-                }
+    attackers
+}
 
-                rank = piece_pos.rank;
-                file = piece_pos.file;
-                break;
+/// Whether any piece of the given color attacks the square
+pub fn is_square_attacked(board: &Board, target: &Position, attacker: Color) -> bool {
+    !attackers_of(board, target, attacker).is_empty()
+}
+
+fn is_en_passant(game: &Game, start: &Position, end: &Position) -> bool {
+    let piece = game.board.get(start.rank, start.file);
+
+    matches!(piece, Some(Piece { kind: PAWN, .. })) && start.file != end.file && game.en_passant_possible == Some(*end)
+}
+
+fn get_en_passant_pos(game: &Game, pawn_pos: &Position, pawn: Color) -> Option<Position> {
+    let target = game.en_passant_possible?;
+    let target_rank = if pawn == BLACK { pawn_pos.rank + 1 } else { pawn_pos.rank.wrapping_sub(1) };
+
+    if target.rank == target_rank && target.file.abs_diff(pawn_pos.file) == 1 {
+        return Some(target);
+    }
+
+    None
+}
+
+fn leaves_king_in_check(game: &Game, start: &Position, end: &Position) -> bool {
+    let piece = match game.board.get(start.rank, start.file) {
+        Some(piece) => piece,
+        None => return false,
+    };
+
+    let mut new_board = Board::make_move(&game.board, *start, end, piece);
+
+    if is_en_passant(game, start, end) {
+        new_board.board[start.rank][end.file] = None;
+    }
+
+    let color = piece.color;
+
+    match find_king(&new_board, color) {
+        Some(king_pos) => is_square_attacked(&new_board, &king_pos, color.opposite()),
+        None => false,
+    }
+}
+
+fn get_vertical_moves(board: &Board, piece_pos: &Position) -> Vec<Position> {
+    let mut positions: Vec<Position> = Vec::new();
+    let mut rank = piece_pos.rank;
+    let file = piece_pos.file;
+
+    loop {
+        if rank == 7 {
+            if (file != piece_pos.file || rank != piece_pos.rank) && is_free_or_enemy(board, piece_pos, &Position::create(7, file)) {
+                positions.push(Position::create(7, file));
             }
 
-            positions.push(Position::create(rank + 1, file - 1));
+            rank = piece_pos.rank;
+
+            break;
+        }
+
+        if rank == piece_pos.rank && file == piece_pos.file {
+            rank += 1;
+            continue;
+        }
+
+        if board.get(rank, file).is_some() {
+            if is_enemy(board, piece_pos, &Position::create(rank, file)) {
+                positions.push(Position::create(rank, file));
+            }
+
+            rank = piece_pos.rank;
+
+            break;
+        }
+
+        positions.push(Position::create(rank, file));
+
+        rank += 1;
+    }
+
+    loop {
+        if rank == 0 {
+            if (file != piece_pos.file || rank != piece_pos.rank) && is_free_or_enemy(board, piece_pos, &Position::create(rank, file)) {
+                positions.push(Position::create(rank, file));
+            }
+
+            break;
+        }
+
+        if rank == piece_pos.rank && file == piece_pos.file {
+            rank -= 1;
+            continue;
+        }
+
+        if board.get(rank, file).is_some() {
+            if is_enemy(board, piece_pos, &Position::create(rank, file)) {
+                positions.push(Position::create(rank, file));
+            }
+
+            break;
+        }
+
+        positions.push(Position::create(rank, file));
+
+        rank -= 1;
+    }
+
+    positions
+}
+
+fn get_diagonal_moves(board: &Board, piece_pos: &Position) -> Vec<Position> {
+    let mut positions: Vec<Position> = Vec::new();
+    let mut rank = piece_pos.rank;
+    let mut file = piece_pos.file;
+
+    if rank > 0 && file > 0 {
+        loop {
+            if board.get(rank - 1, file - 1).is_some() {
+                if is_enemy(board, piece_pos, &Position::create(rank - 1, file - 1)) {
+                    positions.push(Position::create(rank - 1, file - 1));
+                }
+
+                rank = piece_pos.rank;
+                file = piece_pos.file;
+                break;
+            }
+
+            positions.push(Position::create(rank - 1, file - 1));
+
+            rank -= 1;
+            file -= 1;
+
+            if rank == 0 || file == 0 {
+                rank = piece_pos.rank;
+                file = piece_pos.file;
+                break;
+            }
+        }
+    }
+
+    if rank > 0 && file < 7 {
+        loop {
+            if board.get(rank - 1, file + 1).is_some() {
+                if is_enemy(board, piece_pos, &Position::create(rank - 1, file + 1)) {
+                    positions.push(Position::create(rank - 1, file + 1));
+                }
+
+                rank = piece_pos.rank;
+                file = piece_pos.file;
+                break;
+            }
+
+            positions.push(Position::create(rank - 1, file + 1));
+
+            rank -= 1;
+            file += 1;
+
+            if rank == 0 || file == 7 {
+                rank = piece_pos.rank;
+                file = piece_pos.file;
+                break;
+            }
+        }
+    }
+
+    if rank < 7 && file > 0 {
+        loop {
+            if board.get(rank + 1, file - 1).is_some() {
+                if is_enemy(board, piece_pos, &Position::create(rank + 1, file - 1)) {
+                    positions.push(Position::create(rank + 1, file - 1));
+                }
+
+                rank = piece_pos.rank;
+                file = piece_pos.file;
+                break;
+            }
+
+            positions.push(Position::create(rank + 1, file - 1));
+
+            rank += 1;
+            file -= 1;
+
+            if rank == 7 || file == 0 {
+                rank = piece_pos.rank;
+                file = piece_pos.file;
+                break;
+            }
+        }
+    }
+
+    if rank < 7 && file < 7 {
+        loop {
+            if board.get(rank + 1, file + 1).is_some() {
+                if is_enemy(board, piece_pos, &Position::create(rank + 1, file + 1)) {
+                    positions.push(Position::create(rank + 1, file + 1));
+                }
+
+                break;
+            }
+
+            positions.push(Position::create(rank + 1, file + 1));
+
+            rank += 1;
+            file += 1;
+
+            if rank == 7 || file == 7 {
+                break;
+            }
+        }
+    }
+
+    positions
+}
+
+// Exported Functions
+
+pub fn get_pawn_moves(game: &Game, pawn_pos: &Position) -> Vec<Position> {
+    let piece = get_piece_from_position(&game.board, pawn_pos);
+
+    let mut positions: Vec<Position> = Vec::new();
+
+    if piece == Some(Piece::new(PAWN, BLACK)) && pawn_pos.rank < 7 {
+        if game.board.get(pawn_pos.rank + 1,pawn_pos.file).is_none() {
+            if pawn_pos.rank == 1 && game.board.get(3,pawn_pos.file).is_none() {
+                positions.push(Position::create(3, pawn_pos.file)); // 2 step pawn move
+            }
+
+            positions.push(Position::create(pawn_pos.rank + 1, pawn_pos.file)); // 1 step pawn move
+        }
+
+        let capture_positions = get_pawn_capture_pos(&game.board, pawn_pos, BLACK);
+
+        for capture in capture_positions {
+            positions.push(capture);
+        }
+
+        if let Some(en_passant) = get_en_passant_pos(game, pawn_pos, BLACK) {
+            positions.push(en_passant);
+        }
+
+        return positions;
+    } else if piece == Some(Piece::new(PAWN, WHITE)) && pawn_pos.rank > 0 {
+        if game.board.get(pawn_pos.rank - 1, pawn_pos.file).is_none() {
+            if pawn_pos.rank == 6 && game.board.get(4, pawn_pos.file).is_none() {
+                positions.push(Position::create(4, pawn_pos.file)); // 2 step pawn move
+            }
+
+            positions.push(Position::create(pawn_pos.rank - 1, pawn_pos.file)); // 1 step pawn move
+        }
+
+        let capture_positions = get_pawn_capture_pos(&game.board, pawn_pos, WHITE);
+
+        for capture in capture_positions {
+            positions.push(capture);
+        }
+
+        if let Some(en_passant) = get_en_passant_pos(game, pawn_pos, WHITE) {
+            positions.push(en_passant);
+        }
+
+        return positions;
+    }
+
+    positions
+}
+pub fn get_knight_moves(board: &Board, knight_pos: &Position) -> Vec<Position> {
+    let piece = get_piece_from_position(board, knight_pos);
+    let mut positions: Vec<Position> = Vec::new();
+    let rank = knight_pos.rank;
+    let file = knight_pos.file;
+
+    if matches!(piece, Some(Piece { kind: KNIGHT, .. })) {
+        if rank > 1 {
+            if file > 0 {
+                positions.push(Position::create(rank - 2, file - 1));
+            }
+
+            if file < 7 {
+                positions.push(Position::create(rank - 2, file + 1));
+            }
+        }
+
+        if rank < 6 {
+            if file > 0 {
+                positions.push(Position::create(rank + 2, file - 1));
+            }
+
+            if file < 7 {
+                positions.push(Position::create(rank + 2, file + 1));
+            }
+        }
+
+        if file > 1 {
+            if rank > 0 {
+                positions.push(Position::create(rank - 1, file - 2));
+            }
+
+            if rank < 7 {
+                positions.push(Position::create(rank + 1, file - 2));
+            }
+        }
+
+        if file < 6 {
+            if rank > 0 {
+                positions.push(Position::create(rank - 1, file + 2));
+            }
+
+            if rank < 7 {
+                positions.push(Position::create(rank + 1, file + 2));
+            }
+        }
+
+        let mut valid_moves = Vec::new();
+
+        for position in positions {
+            if is_free_or_enemy(board, knight_pos, &position) {
+                valid_moves.push(position);
+            }
+        }
+
+        return valid_moves;
+    }
+
+    positions
+}
+
+pub fn get_bishop_moves(board: &Board, bishop_pos: &Position) -> Vec<Position> {
+    let piece = get_piece_from_position(board, bishop_pos);
+
+    if matches!(piece, Some(Piece { kind: BISHOP, .. })) {
+        return get_diagonal_moves(board, bishop_pos);
+    }
+
+    Vec::new()
+}
+
+pub fn get_queen_moves(board: &Board, queen_pos: &Position) -> Vec<Position> {
+    let piece = get_piece_from_position(board, queen_pos);
+    let mut positions = get_diagonal_moves(board, queen_pos);
+
+    if matches!(piece, Some(Piece { kind: QUEEN, .. })) {
+        let horizontal_moves = get_horizontal_moves(board, queen_pos);
+        let vertical_moves = get_vertical_moves(board, queen_pos);
+
+        for h_move in horizontal_moves {
+            positions.push(h_move);
+        }
+
+        for v_move in vertical_moves {
+            positions.push(v_move);
+        }
+
+        return positions;
+    }
+
+    positions
+}
+
+pub fn get_king_moves(game: &Game, king_pos: &Position) -> Vec<Position> {
+    let piece = get_piece_from_position(&game.board, king_pos);
+
+    if let Some(Piece { kind: KING, color }) = piece {
+        let king_rank = king_pos.rank;
+        let king_file = king_pos.file;
+        let mut positions:Vec<Position> = Vec::new();
+
+        if king_rank > 0 {
+            if king_file > 0 {
+                positions.push(Position::create(king_rank - 1, king_file - 1));
+            }
+
+            if king_file < 7 {
+                positions.push(Position::create(king_rank - 1, king_file + 1));
+            }
+
+            positions.push(Position::create(king_rank - 1, king_file ));
+        }
+
+        if king_rank < 7 {
+            if king_file > 0 {
+                positions.push(Position::create(king_rank + 1, king_file - 1));
+            }
+
+            if king_file < 7 {
+                positions.push(Position::create(king_rank + 1, king_file + 1));
+            }
+
+            positions.push(Position::create(king_rank + 1, king_file ));
+        }
+
+        if king_file > 0 {
+            positions.push(Position::create(king_rank, king_file - 1));
+        }
+
+        if king_file < 7 {
+            positions.push(Position::create(king_rank, king_file + 1));
+        }
+
+        let mut valid_positions: Vec<Position>= Vec::new();
+        let castle_positions = get_castling_position(game, color);
+
+        for position in positions {
+            if is_free_or_enemy(&game.board, king_pos, &position) {
+                valid_positions.push(position)
+            }
+        }
+
+        for castle_position in castle_positions {
+            valid_positions.push(castle_position);
+        }
+
+        return valid_positions;
+    }
+
+    Vec::new()
+}
+
+pub fn get_rook_moves(board: &Board, rook_pos: &Position) -> Vec<Position> {
+    let piece = get_piece_from_position(board, rook_pos);
+    let mut positions = Vec::new();
+
+    if matches!(piece, Some(Piece { kind: ROOK, .. })) {
+        let horizontal_moves = get_horizontal_moves(board, rook_pos);
+        let vertical_moves = get_vertical_moves(board, rook_pos);
+
+        for h_move in horizontal_moves {
+            positions.push(h_move);
+        }
+
+        for v_move in vertical_moves {
+            positions.push(v_move);
+        }
+
+        return positions;
+    }
+
+    positions
+}
+
+/// Returns the legal moves for the piece on the given square, or an error if the square is empty.
+/// Moves that would leave the piece's own king in check are filtered out.
+pub fn get_moves(game: &Game, position: &Position) -> Result<Vec<Position>, ChessError> {
+    if let Some(piece) = game.board.get(position.rank, position.file) {
+        let moves = match piece.kind {
+            PAWN => get_pawn_moves(game, position),
+            ROOK => get_rook_moves(&game.board, position),
+            KNIGHT => get_knight_moves(&game.board, position),
+            BISHOP => get_bishop_moves(&game.board, position),
+            QUEEN => get_queen_moves(&game.board, position),
+            KING => get_king_moves(game, position),
+        };
+
+        let legal_moves = moves
+            .into_iter()
+            .filter(|end| !leaves_king_in_check(game, position, end))
+            .collect();
+
+        return Ok(legal_moves);
+    }
+
+    Err(ChessError::NO_PIECE_ON_SQUARE(*position))
+}
+
+// Turns the destinations of the piece on the square into full moves, with one move per
+// promotion piece when a pawn reaches the last rank
+fn get_moves_from(game: &Game, from: &Position) -> Vec<Move> {
+    let mut moves: Vec<Move> = Vec::new();
+
+    let piece = match game.board.get(from.rank, from.file) {
+        Some(piece) => piece,
+        None => return moves,
+    };
+
+    for to in get_moves(game, from).unwrap_or_default() {
+        let en_passant = is_en_passant(game, from, &to);
+
+        let captured = if en_passant {
+            game.board.get(from.rank, to.file)
+        } else {
+            game.board.get(to.rank, to.file)
+        };
+
+        let castle = if piece.kind == KING && from.file.abs_diff(to.file) == 2 {
+            Some(if to.file == 6 { SHORT } else { LONG })
+        } else {
+            None
+        };
+
+        let chess_move = Move {
+            from: *from,
+            to,
+            piece,
+            captured,
+            promotion: None,
+            double_push: piece.kind == PAWN && from.rank.abs_diff(to.rank) == 2,
+            en_passant,
+            castle,
+        };
+
+        if is_promotion(game, from, &to) {
+            for promotion in [QUEEN, ROOK, BISHOP, KNIGHT] {
+                moves.push(Move {
+                    promotion: Some(Piece::new(promotion, piece.color)),
+                    ..chess_move
+                });
+            }
+        }
+
+        else {
+            moves.push(chess_move);
+        }
+    }
+
+    moves
+}
+
+/// Returns every legal move for the side to move
+pub fn get_all_moves(game: &Game) -> Vec<Move> {
+    if game.is_game_over() {
+        return Vec::new();
+    }
+
+    generate_moves(game)
+}
+
+// Every legal move for the side to move, also when the game has already ended
+fn generate_moves(game: &Game) -> Vec<Move> {
+    let mut legal_moves: Vec<Move> = Vec::new();
+
+    for (rank, row) in game.board.board.iter().enumerate() {
+        for (file, square) in row.iter().enumerate() {
+            if square.map(|piece| piece.color) == Some(game.side_to_move) {
+                legal_moves.extend(get_moves_from(game, &Position::create(rank, file)));
+            }
+        }
+    }
+
+    legal_moves
+}
+
+fn has_legal_moves(game: &Game) -> bool {
+    let color = game.side_to_move();
+
+    for (rank, row) in game.board.board.iter().enumerate() {
+        for (file, square) in row.iter().enumerate() {
+            if square.map(|piece| piece.color) == Some(color) {
+                let moves = get_moves(game, &Position::create(rank, file)).unwrap_or_default();
+
+                if !moves.is_empty() {
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+// Works out check, checkmate and stalemate for the side that is about to move
+fn update_status(game: &mut Game) {
+    let color = game.side_to_move();
+
+    game.in_check = match find_king(&game.board, color) {
+        Some(king_pos) => is_square_attacked(&game.board, &king_pos, color.opposite()),
+        None => false,
+    };
+
+    if !has_legal_moves(game) {
+        game.status = if !game.in_check {
+            STALEMATE
+        } else if color == WHITE {
+            BLACK_HAS_CHECKMATE
+        } else {
+            WHITE_HAS_CHECKMATE
+        };
+
+        return;
+    }
+
+    if is_insufficient_material(&game.board) {
+        game.status = DRAW(INSUFFICIENT_MATERIAL);
+    }
+
+    else if is_dead_position(&game.board) {
+        game.status = DRAW(DEAD_POSITION);
+    }
+
+    else if game.halfmove_clock >= 150 {
+        game.status = DRAW(SEVENTY_FIVE_MOVE_RULE);
+    }
+
+    else if game.repetition_count() >= 5 {
+        game.status = DRAW(FIVEFOLD_REPETITION);
+    }
+
+    else if game.halfmove_clock >= 100 {
+        game.status = claimable_draw_status(color, FIFTY_MOVE_RULE);
+    }
+
+    else if game.repetition_count() >= 3 {
+        game.status = claimable_draw_status(color, THREEFOLD_REPETITION);
+    }
+}
+
+// The game goes on, the player of the color may claim the draw on their move
+fn claimable_draw_status(color: Color, reason: DrawReason) -> Status {
+    if color == WHITE {
+        WHITE_CAN_CLAIM_DRAW(reason)
+    }
+
+    else {
+        BLACK_CAN_CLAIM_DRAW(reason)
+    }
+}
+
+/// Whether neither side has enough material left to checkmate: king against king, a single
+/// minor piece against a bare king, or only bishops that all stand on squares of the same color
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let mut pieces: Vec<(PieceKind, usize)> = Vec::new();
+
+    for (rank, row) in board.board.iter().enumerate() {
+        for (file, square) in row.iter().enumerate() {
+            if let Some(piece) = square {
+                if piece.kind != KING {
+                    pieces.push((piece.kind, (rank + file) % 2));
+                }
+            }
+        }
+    }
+
+    match pieces.as_slice() {
+        [] => true,
+        [(BISHOP, _)] | [(KNIGHT, _)] => true,
+        [(_, square_color), ..] => pieces.iter().all(|(kind, color)| *kind == BISHOP && color == square_color),
+    }
+}
+
+// Only kings and pawns are left, every pawn is blocked by another pawn and no king can ever
+// reach a pawn it is allowed to take. Nothing but the kings can move again, so nobody can win.
+fn is_dead_position(board: &Board) -> bool {
+    let mut pawns: Vec<Position> = Vec::new();
+    let mut kings: Vec<Position> = Vec::new();
+
+    for (rank, row) in board.board.iter().enumerate() {
+        for (file, square) in row.iter().enumerate() {
+            match square.map(|piece| piece.kind) {
+                Some(PAWN) => pawns.push(Position::create(rank, file)),
+                Some(KING) => kings.push(Position::create(rank, file)),
+                None => {}
+                _ => return false,
+            }
+        }
+    }
+
+    if pawns.is_empty() {
+        return false;
+    }
+
+    for pawn_pos in &pawns {
+        let pawn = board.get(pawn_pos.rank, pawn_pos.file).unwrap().color;
+        let ahead = if pawn == WHITE { pawn_pos.rank - 1 } else { pawn_pos.rank + 1 };
+
+        if board.get(ahead, pawn_pos.file).map(|piece| piece.kind) != Some(PAWN) || !get_pawn_capture_pos(board, pawn_pos, pawn).is_empty() {
+            return false;
+        }
+    }
+
+    for king_pos in &kings {
+        let color = board.get(king_pos.rank, king_pos.file).unwrap().color;
+        let enemy_pawn = Some(Piece::new(PAWN, color.opposite()));
+
+        // Only pawns count as guards, they are locked and stay where they are while a king can walk away
+        let guarded_by_pawn = |position: &Position| attackers_of(board, position, color.opposite()).iter().any(|pos| board.get(pos.rank, pos.file) == enemy_pawn);
+
+        // Flood fill every square the king can walk to without stepping next to an enemy pawn's attack
+        let mut reachable = vec![*king_pos];
+        let mut index = 0;
+
+        while index < reachable.len() {
+            let current = reachable[index];
+            index += 1;
+
+            for rank in current.rank.saturating_sub(1)..=(current.rank + 1).min(7) {
+                for file in current.file.saturating_sub(1)..=(current.file + 1).min(7) {
+                    let next = Position::create(rank, file);
+                    let square = board.get(rank, file);
+
+                    if square == enemy_pawn && !guarded_by_pawn(&next) {
+                        return false;
+                    }
+
+                    if square.map(|piece| piece.kind) != Some(PAWN) && !reachable.contains(&next) && !guarded_by_pawn(&next) {
+                        reachable.push(next);
+                    }
+                }
+            }
+        }
+    }
+
+    true
+}
+
+pub(crate) fn can_capture_en_passant(game: &Game, target: &Position) -> bool {
+    let pawn_rank = if game.side_to_move() == WHITE { target.rank + 1 } else { target.rank.wrapping_sub(1) };
+    let pawn = Some(Piece::new(PAWN, game.side_to_move()));
+
+    if pawn_rank > 7 {
+        return false;
+    }
+
+    [target.file.wrapping_sub(1), target.file + 1]
+        .into_iter()
+        .filter(|file| *file <= 7 && game.board.get(pawn_rank, *file) == pawn)
+        .any(|file| get_moves(game, &Position::create(pawn_rank, file)).unwrap_or_default().contains(target))
+}
+
+// A king move loses both castling rights, and a move from or to a corner loses the right for
+// that rook, whether the rook moves away or gets captured
+fn update_castling_rights(game: &mut Game, start: &Position, end: &Position) {
+    match game.board.get(end.rank, end.file) {
+        Some(Piece { kind: KING, color: WHITE }) => {
+            game.white_castle_short = false;
+            game.white_castle_long = false;
+        }
+        Some(Piece { kind: KING, color: BLACK }) => {
+            game.black_castle_short = false;
+            game.black_castle_long = false;
+        }
+        _ => {}
+    }
+
+    for square in [start, end] {
+        match (square.rank, square.file) {
+            (7, 7) => game.white_castle_short = false,
+            (7, 0) => game.white_castle_long = false,
+            (0, 7) => game.black_castle_short = false,
+            (0, 0) => game.black_castle_long = false,
+            _ => {}
+        }
+    }
+}
+
+/// Whether moving the piece from start to end would take a pawn to the last rank,
+/// in which case a promotion piece has to be chosen
+pub fn is_promotion(game: &Game, start: &Position, end: &Position) -> bool {
+    match game.board.get(start.rank, start.file) {
+        Some(Piece { kind: PAWN, color: WHITE }) => end.rank == 0,
+        Some(Piece { kind: PAWN, color: BLACK }) => end.rank == 7,
+        _ => false,
+    }
+}
+
+/// Looks up the legal move from start to end. The promotion piece is one of QUEEN, ROOK, BISHOP
+/// or KNIGHT and is required exactly when a pawn reaches the last rank.
+pub fn find_move(game: &Game, start: &Position, end: &Position, promotion: Option<PieceKind>) -> Result<Move, ChessError> {
+    if game.is_game_over() {
+        return Err(ChessError::GAME_OVER);
+    }
+
+    let piece = match game.board.get(start.rank, start.file) {
+        Some(piece) => piece,
+        None => return Err(ChessError::NO_PIECE_ON_SQUARE(*start)),
+    };
+
+    if piece.color != game.side_to_move {
+        return Err(ChessError::WRONG_TURN(game.side_to_move));
+    }
+
+    let moves: Vec<Move> = get_moves_from(game, start).into_iter().filter(|chess_move| chess_move.to == *end).collect();
+
+    if moves.is_empty() {
+        return Err(ChessError::ILLEGAL_MOVE);
+    }
+
+    match promotion {
+        Some(choice) => moves
+            .into_iter()
+            .find(|chess_move| chess_move.promotion.map(|piece| piece.kind) == Some(choice))
+            .ok_or(ChessError::INVALID_PROMOTION(choice)),
+        None if moves[0].is_promotion() => Err(ChessError::PROMOTION_REQUIRED),
+        None => Ok(moves[0]),
+    }
+}
+
+/// Makes a move given by its squares, see `find_move`
+pub fn make_move_with_promotion(game: &mut Game, start: &Position, end: &Position, promotion: Option<PieceKind>) -> Result<bool, ChessError> {
+    let chess_move = find_move(game, start, end, promotion)?;
+
+    make_move(game, &chess_move)
+}
+
+/// Makes a legal move, usually one returned by `get_all_moves`. Only the squares and the
+/// promotion piece of the move are looked at, the rest is filled in from the position.
+pub fn make_move(game: &mut Game, chess_move: &Move) -> Result<bool, ChessError> {
+    play_move(game, chess_move)?;
+    game.redo_moves.clear();
+
+    Ok(true)
+}
+
+fn play_move(game: &mut Game, chess_move: &Move) -> Result<bool, ChessError> {
+    let chess_move = find_move(game, &chess_move.from, &chess_move.to, chess_move.promotion.map(|piece| piece.kind))?;
+    apply_move(game, chess_move);
+
+    Ok(true)
+}
+
+// Updates the game for a move that is known to be legal
+fn apply_move(game: &mut Game, chess_move: Move) {
+    let start = &chess_move.from;
+    let end = &chess_move.to;
+
+    game.undo_states.push(UndoState {
+        status: game.status,
+        current_move: game.current_move,
+        white_castle_short: game.white_castle_short,
+        white_castle_long: game.white_castle_long,
+        black_castle_short: game.black_castle_short,
+        black_castle_long: game.black_castle_long,
+        en_passant_possible: game.en_passant_possible,
+        in_check: game.in_check,
+        halfmove_clock: game.halfmove_clock,
+        hash: game.hash,
+    });
+    game.moves.push(chess_move);
+
+    // The castling rights and the en passant file are taken out now and added back once they are updated
+    game.hash ^= zobrist::castling(game) ^ zobrist::en_passant(game);
+
+    let placed = chess_move.promotion.unwrap_or(chess_move.piece);
+    let captured_pos = if chess_move.en_passant { Position::create(start.rank, end.file) } else { *end };
+
+    game.hash ^= zobrist::piece(chess_move.piece, start) ^ zobrist::piece(placed, end);
+
+    if let Some(captured) = chess_move.captured {
+        game.hash ^= zobrist::piece(captured, &captured_pos);
+    }
+
+    game.board.board[start.rank][start.file] = None;
+    game.board.board[captured_pos.rank][captured_pos.file] = None;
+    game.board.board[end.rank][end.file] = Some(placed);
+
+    if chess_move.is_castle() {
+        let (rook_start, rook_end) = if chess_move.castle == Some(SHORT) { (7, 5) } else { (0, 3) };
+        let rook = Piece::new(ROOK, chess_move.piece.color);
+
+        game.hash ^= zobrist::piece(rook, &Position::create(end.rank, rook_start)) ^ zobrist::piece(rook, &Position::create(end.rank, rook_end));
+
+        game.board.board[end.rank][rook_end] = game.board.board[end.rank][rook_start];
+        game.board.board[end.rank][rook_start] = None;
+    }
+
+    update_castling_rights(game, start, end);
+
+    if chess_move.piece.kind == PAWN || chess_move.is_capture() {
+        game.halfmove_clock = 0;
+    }
+
+    else {
+        game.halfmove_clock += 1;
+    }
+
+    if chess_move.piece.color == BLACK {
+        game.fullmove_number += 1;
+    }
+
+    if chess_move.double_push {
+        game.en_passant_possible = Some(Position::create((start.rank + end.rank) / 2, start.file));
+    }
+
+    else {
+        game.en_passant_possible = None;
+    }
+
+    game.current_move = Some(chess_move);
+
+    if game.side_to_move() == WHITE {
+        game.status = BLACK_TO_MOVE;
+    }
+
+    else {
+        game.status = WHITE_TO_MOVE;
+    }
+
+    game.side_to_move = game.side_to_move.opposite();
+
+    game.hash ^= zobrist::castling(game) ^ zobrist::en_passant(game) ^ zobrist::side();
+    game.position_history.push(game.hash);
+
+    update_status(game);
+}
+
+pub fn run() {
+    let mut game = Game::new();
+
+    while !game.is_game_over() {
+        game.board.print();
+        println!();
+
+        let mut input = String::new();
+
+        println!("Enter move");
+
+        // Stops at the end of the input or when it can't be read
+        match stdin().read_line(&mut input) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        let result = match game.parse_san(&input) {
+            Ok(chess_move) => make_move(&mut game, &chess_move).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        match result {
+            Ok(_) => println!("Success"),
+            Err(e) => println!("{}", e)
+        }
+    }
+
+    if game.is_game_over() {
+        game.board.print();
+        println!("{:?}", game.status());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perft_depth_1() {
+        let mut game = Game::new();
+        let nodes = game.perft(1);
+        assert_eq!(nodes, 20, "Perft Depth 1 failed: Expected 20 nodes, got {}", nodes);
+    }
+
+    #[test]
+    fn test_perft_depth_2() {
+        let mut game = Game::new();
+        let nodes = game.perft(2);
+        assert_eq!(nodes, 400, "Perft Depth 2 failed: Expected 400 nodes, got {}", nodes);
+    }
+
+    #[test]
+    fn test_perft_depth_3() {
+        let mut game = Game::new();
+        let nodes = game.perft(3);
+        assert_eq!(nodes, 8902, "Perft Depth 3 failed: Expected 8902 nodes, got {}", nodes);
+    }
+
+    fn play(game: &mut Game, moves: &[(usize, usize, usize, usize)]) {
+        for (start_rank, start_file, end_rank, end_file) in moves {
+            make_move_with_promotion(game, &Position::create(*start_rank, *start_file), &Position::create(*end_rank, *end_file), None).unwrap();
+        }
+    }
+
+    fn game_from_fen(fen: &str) -> Game {
+        Game::from_fen(fen).unwrap()
+    }
+
+    #[test]
+    fn test_pinned_piece_cannot_leave_pin() {
+        // White knight on e2 is pinned to the king by the rook on e8
+        let game = game_from_fen("4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1");
+        let moves = get_moves(&game, &Position::create(6, 4)).unwrap();
+        assert!(moves.is_empty(), "Pinned knight should have no moves, got {:?}", moves);
+    }
+
+    #[test]
+    fn test_king_cannot_step_into_attack() {
+        let game = game_from_fen("3r2k1/8/8/8/8/8/8/4K3 w - - 0 1");
+        let moves = get_moves(&game, &Position::create(7, 4)).unwrap();
+        assert!(!moves.contains(&Position::create(7, 3)));
+        assert!(!moves.contains(&Position::create(6, 3)));
+        assert!(moves.contains(&Position::create(6, 4)));
+    }
+
+    #[test]
+    fn test_double_check_only_king_moves() {
+        // Rook on e8 and bishop on b4 both give check, the white rook could block either one alone
+        let game = game_from_fen("4r1k1/8/8/8/1b6/7R/8/4K3 w - - 0 1");
+        let all_moves = get_all_moves(&game);
+
+        assert!(!all_moves.is_empty());
+
+        for chess_move in all_moves {
+            assert_eq!(chess_move.from, Position::create(7, 4), "Only the king may move in double check, got {:?}", chess_move);
+        }
+    }
+
+    #[test]
+    fn test_fools_mate_is_checkmate() {
+        let mut game = Game::new();
+        play(&mut game, &[(6, 5, 5, 5), (1, 4, 3, 4), (6, 6, 4, 6), (0, 3, 4, 7)]);
+
+        assert_eq!(*game.status(), BLACK_HAS_CHECKMATE);
+        assert!(game.in_check());
+        assert!(make_move_with_promotion(&mut game, &Position::create(6, 0), &Position::create(5, 0), None).is_err());
+    }
+
+    #[test]
+    fn test_stalemate_and_check_flags() {
+        let mut game = game_from_fen("k7/8/3Q4/8/8/8/8/4K3 w - - 0 1");
+        play(&mut game, &[(2, 3, 2, 1)]);
+
+        assert_eq!(*game.status(), STALEMATE);
+        assert!(!game.in_check());
+
+        let mut game = game_from_fen("k7/8/3Q4/8/8/8/8/4K3 w - - 0 1");
+        play(&mut game, &[(2, 3, 2, 0)]);
+
+        assert_eq!(*game.status(), BLACK_TO_MOVE);
+        assert!(game.in_check());
+    }
+
+    #[test]
+    fn test_en_passant_capture() {
+        let mut game = game_from_fen("4k3/8/8/8/1p6/8/P7/4K3 w - - 0 1");
+        play(&mut game, &[(6, 0, 4, 0)]);
+
+        assert_eq!(game.en_passant_possible, Some(Position::create(5, 0)));
+        assert!(get_moves(&game, &Position::create(4, 1)).unwrap().contains(&Position::create(5, 0)));
+
+        play(&mut game, &[(4, 1, 5, 0)]);
+
+        assert_eq!(game.board.get(4, 0), None);
+        assert_eq!(game.board.get(5, 0), Piece::from_char('p'));
+        assert_eq!(game.en_passant_possible, None);
+    }
+
+    #[test]
+    fn test_en_passant_only_on_next_move() {
+        let mut game = game_from_fen("4k3/8/8/8/1p6/8/P7/4K3 w - - 0 1");
+        play(&mut game, &[(6, 0, 4, 0), (0, 4, 0, 3), (7, 4, 7, 3)]);
+
+        assert!(!get_moves(&game, &Position::create(4, 1)).unwrap().contains(&Position::create(5, 0)));
+    }
+
+    #[test]
+    fn test_en_passant_cannot_expose_king() {
+        // Capturing en passant would clear the fourth rank between the rook and the black king
+        let mut game = game_from_fen("8/8/8/8/k2p3R/8/4P3/4K3 w - - 0 1");
+        play(&mut game, &[(6, 4, 4, 4)]);
+
+        assert!(!get_moves(&game, &Position::create(4, 3)).unwrap().contains(&Position::create(5, 4)));
+    }
+
+    #[test]
+    fn test_promotion_requires_piece() {
+        let mut game = game_from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+        let start = Position::create(1, 1);
+        let end = Position::create(0, 1);
+
+        assert!(is_promotion(&game, &start, &end));
+        assert!(make_move_with_promotion(&mut game, &start, &end, None).is_err());
+        assert!(make_move_with_promotion(&mut game, &start, &end, Some(KING)).is_err());
+        assert_eq!(game.board.get(1, 1), Piece::from_char('P'));
+
+        make_move_with_promotion(&mut game, &start, &end, Some(KNIGHT)).unwrap();
+
+        assert_eq!(game.board.get(0, 1), Piece::from_char('N'));
+        assert_eq!(game.current_move().unwrap().promotion, Piece::from_char('N'));
+    }
+
+    #[test]
+    fn test_promotion_gives_check() {
+        let mut game = game_from_fen("8/4P3/8/8/k7/8/8/4K3 w - - 0 1");
+        make_move_with_promotion(&mut game, &Position::create(1, 4), &Position::create(0, 4), Some(QUEEN)).unwrap();
+
+        assert_eq!(game.board().get(0, 4), Piece::from_char('Q'));
+        assert!(is_square_attacked(game.board(), &Position::create(4, 0), WHITE));
+        assert!(game.in_check());
+    }
+
+    #[test]
+    fn test_castling_moves_rook_and_clears_rights() {
+        let mut game = game_from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1");
+        play(&mut game, &[(7, 4, 7, 2)]);
+
+        assert_eq!(game.board.get(7, 2), Piece::from_char('K'));
+        assert_eq!(game.board.get(7, 3), Piece::from_char('R'));
+        assert_eq!(game.board.get(7, 0), None);
+        assert_eq!(game.current_move().unwrap().castle, Some(LONG));
+        assert!(!game.white_castle_short && !game.white_castle_long);
+
+        play(&mut game, &[(0, 4, 0, 6)]);
+
+        assert_eq!(game.board.get(0, 6), Piece::from_char('k'));
+        assert_eq!(game.board.get(0, 5), Piece::from_char('r'));
+        assert_eq!(game.board.get(0, 7), None);
+    }
+
+    #[test]
+    fn test_castling_rights_lost_on_rook_move_and_capture() {
+        let mut game = game_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        play(&mut game, &[(7, 7, 0, 7)]);
+
+        assert!(!game.white_castle_short && game.white_castle_long);
+        assert!(!game.black_castle_short && game.black_castle_long);
+        assert!(!get_moves(&game, &Position::create(0, 4)).unwrap().contains(&Position::create(0, 6)));
+    }
+
+    #[test]
+    fn test_cannot_castle_through_or_out_of_check() {
+        // The bishop on a6 covers f1
+        let game = game_from_fen("4k3/8/b7/8/8/8/8/R3K2R w KQ - 0 1");
+        let moves = get_moves(&game, &Position::create(7, 4)).unwrap();
+
+        assert!(!moves.contains(&Position::create(7, 6)));
+        assert!(moves.contains(&Position::create(7, 2)));
+
+        let game = game_from_fen("4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1");
+        let moves = get_moves(&game, &Position::create(7, 4)).unwrap();
+
+        assert!(!moves.contains(&Position::create(7, 6)));
+        assert!(!moves.contains(&Position::create(7, 2)));
+    }
+
+    #[test]
+    fn test_attackers_of_square() {
+        let board = Board::create_from_fen("4k3/8/8/2n5/8/1Q1p4/8/2RK4");
+        let mut attackers = attackers_of(&board, &Position::create(6, 2), WHITE);
+        attackers.sort_by_key(|pos| (pos.rank, pos.file));
+
+        // c2 is covered by the queen, the rook, and the king while the d3 pawn belongs to black
+        assert_eq!(attackers, vec![Position::create(5, 1), Position::create(7, 2), Position::create(7, 3)]);
+        assert!(is_square_attacked(&board, &Position::create(6, 2), BLACK));
+        assert!(!is_square_attacked(&board, &Position::create(2, 0), WHITE));
+    }
+
+    #[test]
+    fn test_pawn_attacks_empty_squares() {
+        let board = Board::create_from_fen("4k3/8/8/8/3p4/8/4P3/4K3");
+
+        assert_eq!(attackers_of(&board, &Position::create(5, 3), WHITE), vec![Position::create(6, 4)]);
+        assert_eq!(attackers_of(&board, &Position::create(5, 4), BLACK), vec![Position::create(4, 3)]);
+        assert!(!is_square_attacked(&board, &Position::create(5, 4), WHITE));
+    }
+
+    #[test]
+    fn test_move_counters() {
+        let mut game = Game::new();
+        play(&mut game, &[(7, 6, 5, 5), (0, 6, 2, 5), (5, 5, 7, 6)]);
+
+        assert_eq!(game.halfmove_clock(), 3);
+        assert_eq!(game.fullmove_number(), 2);
+
+        play(&mut game, &[(1, 4, 3, 4)]);
+
+        assert_eq!(game.halfmove_clock(), 0);
+        assert_eq!(game.fullmove_number(), 3);
+    }
+
+    #[test]
+    fn test_fifty_and_seventy_five_move_rule() {
+        let mut game = game_from_fen("k7/8/8/8/8/8/8/1R5K w - - 99 50");
+
+        assert_eq!(game.claimable_draw(), None);
+        assert!(game.claim_draw().is_err());
+
+        play(&mut game, &[(7, 1, 7, 2)]);
+
+        assert_eq!(game.claimable_draw(), Some(FIFTY_MOVE_RULE));
+        assert_eq!(*game.status(), BLACK_CAN_CLAIM_DRAW(FIFTY_MOVE_RULE));
+        assert!(!game.is_game_over());
 
-            rank = rank + 1;
-            file = file - 1;
+        game.halfmove_clock = 149;
+        play(&mut game, &[(0, 0, 1, 0)]);
 
-            if rank == 7 || file == 0 {
-                rank = piece_pos.rank;
-                file = piece_pos.file;
-                break;
-            }
-        }
+        assert_eq!(*game.status(), DRAW(SEVENTY_FIVE_MOVE_RULE));
+        assert!(game.is_game_over());
     }
 
-    if rank < 7 && file < 7 {
-        loop {
-            if board.get(rank + 1, file + 1) != '-' {
-                if is_enemy(board, piece_pos, &Position::create(rank + 1, file + 1)) {
-                    positions.push(Position::create(rank + 1, file + 1));
-                }
+    #[test]
+    fn test_threefold_and_fivefold_repetition() {
+        let mut game = Game::new();
+        let knight_shuffle = [(7, 6, 5, 5), (0, 6, 2, 5), (5, 5, 7, 6), (2, 5, 0, 6)];
 
-                rank = piece_pos.rank;
-                file = piece_pos.file;
-                break;
-            }
+        play(&mut game, &knight_shuffle);
+        assert_eq!(game.repetition_count(), 2);
+        assert_eq!(game.claimable_draw(), None);
 
-            positions.push(Position::create(rank + 1, file + 1));
+        play(&mut game, &knight_shuffle);
+        assert_eq!(game.claimable_draw(), Some(THREEFOLD_REPETITION));
+        assert_eq!(*game.status(), WHITE_CAN_CLAIM_DRAW(THREEFOLD_REPETITION));
 
-            rank = rank + 1;
-            file = file + 1;
+        play(&mut game, &knight_shuffle);
+        assert_eq!(*game.status(), WHITE_CAN_CLAIM_DRAW(THREEFOLD_REPETITION));
 
-            if rank == 7 || file == 7 {
-                rank = piece_pos.rank;
-                file = piece_pos.file;
-                break;
-            }
-        }
+        play(&mut game, &knight_shuffle);
+        assert_eq!(*game.status(), DRAW(FIVEFOLD_REPETITION));
     }
 
-    positions
-}
+    #[test]
+    fn test_repetition_respects_castling_rights() {
+        let mut game = game_from_fen("r3k3/8/8/8/8/8/8/4K2R w K - 0 1");
 
-// Exported Functions
+        // The first king trip loses the castling right, so the starting position never repeats
+        let king_shuffle = [(7, 4, 7, 5), (0, 4, 0, 5), (7, 5, 7, 4), (0, 5, 0, 4)];
+        play(&mut game, &king_shuffle);
+        play(&mut game, &king_shuffle);
 
-pub fn get_pawn_moves(game: &Game, pawn_pos: &Position) -> Vec<Position> {
-    let piece = get_piece_from_position(&game.board, &pawn_pos);
+        assert_eq!(game.repetition_count(), 2);
+    }
 
-    let mut positions: Vec<Position> = Vec::new();
-    println!("{:?}", pawn_pos);
-    if piece == 'p' {
-        if (game.board.get(pawn_pos.rank + 1,pawn_pos.file) == '-') {
-            if pawn_pos.rank == 1 && game.board.get(3,pawn_pos.file) == '-' {
-                positions.push(Position::create(3, pawn_pos.file)); // 2 step pawn move
-            }
+    #[test]
+    fn test_insufficient_material() {
+        assert!(is_insufficient_material(&Board::create_from_fen("4k3/8/8/8/8/8/8/4K3")));
+        assert!(is_insufficient_material(&Board::create_from_fen("4k3/8/8/8/8/8/8/2B1K3")));
+        assert!(is_insufficient_material(&Board::create_from_fen("4k3/8/8/8/8/8/8/1N2K3")));
+        assert!(is_insufficient_material(&Board::create_from_fen("2b1k3/8/8/8/8/8/8/3BK3")));
+        assert!(!is_insufficient_material(&Board::create_from_fen("3bk3/8/8/8/8/8/8/3BK3")));
+        assert!(!is_insufficient_material(&Board::create_from_fen("4k3/8/8/8/8/8/8/1NN1K3")));
+        assert!(!is_insufficient_material(&Board::create_from_fen("4k3/8/8/8/8/8/4P3/4K3")));
+        assert!(!is_insufficient_material(&Board::create()));
+    }
 
-            positions.push(Position::create(pawn_pos.rank + 1, pawn_pos.file)); // 1 step pawn move
+    #[test]
+    fn test_capture_into_insufficient_material_is_drawn() {
+        let mut game = game_from_fen("4k3/8/8/8/8/8/4r3/4KB2 w - - 0 1");
+        play(&mut game, &[(7, 4, 6, 4)]);
 
-            let capture_positions = get_pawn_capture_pos(&game.board, pawn_pos, 'p');
+        assert_eq!(*game.status(), DRAW(INSUFFICIENT_MATERIAL));
+    }
 
-            for capture in capture_positions {
-                positions.push(capture);
-            }
+    #[test]
+    fn test_locked_pawn_chain_is_dead() {
+        assert!(is_dead_position(&Board::create_from_fen("8/4k3/8/p1p1p1p1/P1P1P1P1/8/8/4K3")));
 
-            return positions;
-        }
-    } else if piece == 'P' {
-        if game.board.get(pawn_pos.rank - 1, pawn_pos.file) == '-' {
-            if pawn_pos.rank == 6 && game.board.get(4, pawn_pos.rank) == '-' {
-                positions.push(Position::create(4, pawn_pos.file)); // 2 step pawn move
-            }
+        // The white king can walk around the h-side and take the e5 pawn
+        assert!(!is_dead_position(&Board::create_from_fen("8/4k3/8/p1p1p3/P1P1P3/8/8/4K3")));
 
-            positions.push(Position::create(pawn_pos.rank - 1, pawn_pos.file)); // 1 step pawn move
+        // The pawns are blocked but can still capture each other
+        assert!(!is_dead_position(&Board::create_from_fen("8/4k3/8/pppppppp/PPPPPPPP/8/8/4K3")));
+        assert!(!is_dead_position(&Board::create_from_fen("8/4k3/8/p7/1P6/8/8/4K3")));
 
-            let capture_positions = get_pawn_capture_pos(&game.board, pawn_pos, 'P');
+        // A pawn guarded only by its king can still be won
+        assert!(!is_dead_position(&Board::create_from_fen("8/8/4k3/3p4/3P4/4K3/8/8")));
+        assert!(!is_dead_position(&Board::create_from_fen("8/8/2k5/3p4/3P4/2K5/8/8")));
 
-            for capture in capture_positions {
-                positions.push(capture);
-            }
+        // Taking the last knight leaves only the locked chain
+        let mut game = game_from_fen("8/3Nk3/1p1p1p1p/pPpPpPpP/P1P1P1P1/8/8/3K4 b - - 0 1");
+        play(&mut game, &[(1, 4, 1, 3)]);
 
-            return positions;
-        }
+        assert_eq!(*game.status(), DRAW(DEAD_POSITION));
     }
 
-    positions
-}
-pub fn get_knight_moves(board: &Board, knight_pos: &Position) -> Vec<Position> {
-    let piece = get_piece_from_position(&board, &knight_pos);
-    let mut positions: Vec<Position> = Vec::new();
-    let rank = knight_pos.rank;
-    let file = knight_pos.file;
-
-    if piece == 'n' || piece == 'N' {
-        if rank > 1 {
-            if file > 0 {
-                positions.push(Position::create(rank - 2, file - 1));
-            }
+    #[test]
+    fn test_game_from_fen() {
+        let game = Game::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K3 w Qk d6 0 42").unwrap();
 
-            if file < 7 {
-                positions.push(Position::create(rank - 2, file + 1));
-            }
-        }
+        assert_eq!(*game.status(), WHITE_TO_MOVE);
+        assert!(!game.white_castle_short && game.white_castle_long);
+        assert!(game.black_castle_short && !game.black_castle_long);
+        assert_eq!(game.en_passant_possible, Some(Position::create(2, 3)));
+        assert_eq!(game.halfmove_clock(), 0);
+        assert_eq!(game.fullmove_number(), 42);
+        assert!(get_moves(&game, &Position::create(3, 4)).unwrap().contains(&Position::create(2, 3)));
 
-        if rank < 6 {
-            if file > 0 {
-                positions.push(Position::create(rank + 2, file - 1));
-            }
+        let game = Game::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
 
-            if file < 7 {
-                positions.push(Position::create(rank + 2, file + 1));
-            }
-        }
+        assert_eq!(*game.status(), BLACK_HAS_CHECKMATE);
+        assert!(game.in_check());
 
-        if file > 1 {
-            if rank > 0 {
-                positions.push(Position::create(rank - 1, file - 2));
-            }
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
 
-            if rank < 7 {
-                positions.push(Position::create(rank + 1, file - 2));
-            }
-        }
+        assert_eq!(*game.status(), DRAW(INSUFFICIENT_MATERIAL));
+        assert_eq!(game.fullmove_number(), 1);
+    }
 
-        if file < 6 {
-            if rank > 0 {
-                positions.push(Position::create(rank - 1, file + 2));
-            }
+    #[test]
+    fn test_game_from_fen_errors() {
+        assert_eq!(Game::from_fen("8/8/8/8/8/8/8/8 w").err(), Some(FenError::MISSING_FIELDS));
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 x - - 0 1").err(), Some(FenError::INVALID_SIDE_TO_MOVE("x".to_string())));
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1 extra").err(), Some(FenError::TOO_MANY_FIELDS(7)));
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w KX - 0 1").err(), Some(FenError::INVALID_CASTLING("KX".to_string())));
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w KQkq - 0 1").err(), Some(FenError::INVALID_CASTLING("KQkq".to_string())));
+        assert_eq!(Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KKq - 0 1").err(), Some(FenError::INVALID_CASTLING("KKq".to_string())));
+        assert_eq!(Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w qkQK - 0 1").err(), Some(FenError::INVALID_CASTLING("qkQK".to_string())));
+        assert_eq!(Game::from_fen("r3k2r/8/8/8/8/8/8/R3K1R1 w K - 0 1").err(), Some(FenError::INVALID_CASTLING("K".to_string())));
+        assert!(Game::from_fen("r3k2r/8/8/8/8/8/8/R3K1R1 w Qkq - 0 1").is_ok());
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - e4 0 1").err(), Some(FenError::INVALID_EN_PASSANT("e4".to_string())));
+        assert_eq!(Game::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 3 1").err(), Some(FenError::INVALID_EN_PASSANT("d6".to_string())));
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - x 1").err(), Some(FenError::INVALID_HALFMOVE_CLOCK("x".to_string())));
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - +3 1").err(), Some(FenError::INVALID_HALFMOVE_CLOCK("+3".to_string())));
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 +1").err(), Some(FenError::INVALID_FULLMOVE_NUMBER("+1".to_string())));
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 0").err(), Some(FenError::INVALID_FULLMOVE_NUMBER("0".to_string())));
+    }
 
-            if rank < 7 {
-                positions.push(Position::create(rank + 1, file + 2));
-            }
+    #[test]
+    fn test_fen_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/8/8/3pP3/8/8/8/R3K3 w Qk d6 0 42",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 60",
+        ];
+
+        for fen in fens {
+            assert_eq!(Game::from_fen(fen).unwrap().to_fen(), fen);
         }
 
-        let mut valid_moves = Vec::new();
-
-        for position in positions {
-            let newRank = position.rank;
-            let newFile = position.file;
+        assert_eq!(Board::create().to_fen(), STARTING_FEN);
+    }
 
-            if (board.get(position.rank, position.file) == '-') {
-                valid_moves.push(position);
-            }
+    #[test]
+    fn test_to_fen_after_moves() {
+        let mut game = Game::new();
+        play(&mut game, &[(6, 4, 4, 4), (1, 2, 3, 2), (7, 6, 5, 5)]);
 
-            else if board.get(position.rank, position.file).is_lowercase() && board.get(knight_pos.rank, knight_pos.file).is_uppercase() {
-                valid_moves.push(position);
-            }
+        assert_eq!(game.to_fen(), "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
 
-            else if board.get(position.rank, position.file).is_uppercase() && board.get(knight_pos.rank, knight_pos.file).is_lowercase() {
-                valid_moves.push(position);
-            }
-        }
+        let mut game = Game::from_fen("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2").unwrap();
+        play(&mut game, &[(0, 3, 4, 7)]);
 
-        return valid_moves;
+        assert_eq!(*game.status(), BLACK_HAS_CHECKMATE);
+        assert_eq!(game.to_fen(), "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
     }
 
-    positions
-}
-
-pub fn get_bishop_moves(board: &Board, bishop_pos: &Position) -> Vec<Position> {
-    let piece = get_piece_from_position(&board, &bishop_pos);
+    #[test]
+    fn test_fen_placement_errors() {
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8").err(), Some(FenError::WRONG_RANK_COUNT(7)));
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K3/8").err(), Some(FenError::WRONG_RANK_COUNT(9)));
+        assert_eq!(Board::from_fen("4k3/8/8/7/8/8/8/4K3").err(), Some(FenError::WRONG_RANK_WIDTH(5, 7)));
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/PPPPPPPPP/4K3").err(), Some(FenError::WRONG_RANK_WIDTH(2, 9)));
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K2X").err(), Some(FenError::INVALID_CHARACTER('X')));
+        assert_eq!(Board::from_fen("4k3/8/8/44/8/8/8/4K3").err(), Some(FenError::CONSECUTIVE_DIGITS(5)));
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/11111111/4K3").err(), Some(FenError::CONSECUTIVE_DIGITS(2)));
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/8").err(), Some(FenError::WRONG_KING_COUNT(WHITE, 0)));
+        assert_eq!(Board::from_fen("3kk3/8/8/8/8/8/8/4K3").err(), Some(FenError::WRONG_KING_COUNT(BLACK, 2)));
+        assert_eq!(Board::from_fen("4k2P/8/8/8/8/8/8/4K3").err(), Some(FenError::PAWN_ON_BACK_RANK("h8".to_string())));
+        assert!(Board::from_fen(STARTING_FEN).is_ok());
+    }
 
-    if piece == 'b' || piece == 'B' {
-        return get_diagonal_moves(&board, &bishop_pos);
+    #[test]
+    fn test_fen_position_errors() {
+        assert_eq!(Game::from_fen("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1").err(), Some(FenError::OPPONENT_IN_CHECK));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/4R3/4K3 b - - 0 1").is_ok());
+
+        // The pawn on d5 could only have just moved there with white to move, not black
+        assert_eq!(Game::from_fen("4k3/8/8/3pP3/8/8/8/4K3 b - d6 0 1").err(), Some(FenError::INVALID_EN_PASSANT("d6".to_string())));
+        assert_eq!(Game::from_fen("4k3/8/8/4P3/8/8/8/4K3 w - d6 0 1").err(), Some(FenError::INVALID_EN_PASSANT("d6".to_string())));
+        assert_eq!(Game::from_fen("4k3/3p4/8/3pP3/8/8/8/4K3 w - d6 0 1").err(), Some(FenError::INVALID_EN_PASSANT("d6".to_string())));
+        assert!(Game::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").is_ok());
     }
 
-    Vec::new()
-}
+    #[test]
+    fn test_make_move_errors() {
+        let mut game = Game::new();
 
-pub fn get_queen_moves(board: &Board, queen_pos: &Position) -> Vec<Position> {
-    let piece = get_piece_from_position(&board, &queen_pos);
-    let mut positions = get_diagonal_moves(&board, &queen_pos);
+        assert_eq!(make_move_with_promotion(&mut game, &Position::create(4, 4), &Position::create(3, 4), None), Err(ChessError::NO_PIECE_ON_SQUARE(Position::create(4, 4))));
+        assert_eq!(make_move_with_promotion(&mut game, &Position::create(1, 4), &Position::create(3, 4), None), Err(ChessError::WRONG_TURN(WHITE)));
+        assert_eq!(make_move_with_promotion(&mut game, &Position::create(6, 4), &Position::create(3, 4), None), Err(ChessError::ILLEGAL_MOVE));
+        assert_eq!(get_moves(&game, &Position::create(4, 4)), Err(ChessError::NO_PIECE_ON_SQUARE(Position::create(4, 4))));
+        assert_eq!(game.claim_draw(), Err(ChessError::NO_DRAW_TO_CLAIM));
 
-    if piece == 'q' || piece == 'Q' {
-        let horizontal_moves = get_horizontal_moves(&board, &queen_pos);
-        let vertical_moves = get_vertical_moves(&board, &queen_pos);
+        let mut game = game_from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
 
-        for h_move in horizontal_moves {
-            positions.push(h_move);
-        }
+        assert_eq!(make_move_with_promotion(&mut game, &Position::create(1, 1), &Position::create(0, 1), None), Err(ChessError::PROMOTION_REQUIRED));
+        assert_eq!(make_move_with_promotion(&mut game, &Position::create(1, 1), &Position::create(0, 1), Some(KING)), Err(ChessError::INVALID_PROMOTION(KING)));
+        assert_eq!(make_move_with_promotion(&mut game, &Position::create(7, 4), &Position::create(7, 3), Some(QUEEN)), Err(ChessError::INVALID_PROMOTION(QUEEN)));
 
-        for v_move in vertical_moves {
-            positions.push(v_move);
-        }
+        let mut game = Game::from_fen("4k3/4Q3/4K3/8/8/8/8/8 b - - 0 1").unwrap();
 
-        return positions;
+        assert_eq!(make_move_with_promotion(&mut game, &Position::create(0, 4), &Position::create(0, 3), None), Err(ChessError::GAME_OVER));
+        assert_eq!(ChessError::GAME_OVER.to_string(), "Error: The game is already over");
     }
 
-    positions
-}
+    #[test]
+    fn test_get_all_moves_returns_full_moves() {
+        let game = Game::from_fen("r3k3/1P6/8/3pP3/8/8/8/R3K2R w KQq d6 0 1").unwrap();
+        let moves = get_all_moves(&game);
 
-pub fn get_king_moves(game: &Game, king_pos: &Position) -> Vec<Position> {
-    let piece = get_piece_from_position(&game.board, &king_pos);
+        let promotions: Vec<&Move> = moves.iter().filter(|chess_move| chess_move.is_promotion()).collect();
+        assert_eq!(promotions.len(), 8);
+        assert!(promotions.iter().any(|chess_move| chess_move.to == Position::create(0, 0) && chess_move.captured == Piece::from_char('r') && chess_move.promotion == Piece::from_char('Q')));
 
-    if piece == 'k' || piece == 'K' {
-        let king_rank = king_pos.rank;
-        let king_file = king_pos.file;
-        let mut positions:Vec<Position> = Vec::new();
+        let en_passant = moves.iter().find(|chess_move| chess_move.en_passant).unwrap();
+        assert_eq!((en_passant.from, en_passant.to, en_passant.captured), (Position::create(3, 4), Position::create(2, 3), Piece::from_char('p')));
 
-        if king_rank > 0 {
-            if king_file > 0 {
-                positions.push(Position::create(king_rank - 1, king_file - 1));
-            }
+        let castles: Vec<Option<CastleSide>> = moves.iter().filter(|chess_move| chess_move.is_castle()).map(|chess_move| chess_move.castle).collect();
+        assert_eq!(castles.len(), 2);
+        assert!(castles.contains(&Some(SHORT)) && castles.contains(&Some(LONG)));
+    }
 
-            if king_file < 7 {
-                positions.push(Position::create(king_rank - 1, king_file + 1));
-            }
+    #[test]
+    fn test_make_move_accepts_moves() {
+        let mut game = Game::new();
+        let double_push = get_all_moves(&game).into_iter().find(|chess_move| chess_move.to == Position::create(4, 4)).unwrap();
 
-            positions.push(Position::create(king_rank - 1, king_file ));
-        }
+        assert!(double_push.double_push);
+        assert_eq!(double_push.piece, Piece::new(PAWN, WHITE));
 
-        if king_rank < 7 {
-            if king_file > 0 {
-                positions.push(Position::create(king_rank + 1, king_file - 1));
-            }
+        make_move(&mut game, &double_push).unwrap();
 
-            if king_file < 7 {
-                positions.push(Position::create(king_rank + 1, king_file + 1));
-            }
+        assert_eq!(game.current_move(), Some(&double_push));
+        assert_eq!(game.en_passant_possible, Some(Position::create(5, 4)));
+        assert_eq!(make_move(&mut game, &double_push), Err(ChessError::NO_PIECE_ON_SQUARE(Position::create(6, 4))));
+    }
 
-            positions.push(Position::create(king_rank + 1, king_file ));
+    #[test]
+    fn test_piece_char_conversion() {
+        assert_eq!(Piece::from_char('Q'), Some(Piece::new(QUEEN, WHITE)));
+        assert_eq!(Piece::from_char('n'), Some(Piece::new(KNIGHT, BLACK)));
+        assert_eq!(Piece::from_char('-'), None);
+        assert_eq!(Piece::from_char('x'), None);
+
+        for symbol in ['P', 'N', 'B', 'R', 'Q', 'K', 'p', 'n', 'b', 'r', 'q', 'k'] {
+            assert_eq!(Piece::from_char(symbol).unwrap().to_char(), symbol);
         }
+    }
 
-        if king_file > 0 {
-            positions.push(Position::create(king_rank, king_file - 1));
-        }
+    #[test]
+    fn test_board_holds_typed_pieces() {
+        let game = Game::new();
+
+        assert_eq!(game.board.get(0, 4), Some(Piece::new(KING, BLACK)));
+        assert_eq!(game.board.get(7, 3), Some(Piece::new(QUEEN, WHITE)));
+        assert_eq!(game.board.get(4, 4), None);
+
+        // An empty square is neither a friend nor an enemy of any piece
+        let from = Position::create(7, 1);
+        assert!(!is_enemy(&game.board, &from, &Position::create(4, 4)));
+        assert!(!has_enemy_piece(&game.board, &Position::create(4, 4), WHITE));
+        assert!(has_enemy_piece(&game.board, &Position::create(1, 4), WHITE));
+    }
 
-        if king_file < 7 {
-            positions.push(Position::create(king_rank, king_file + 1));
+    #[test]
+    fn test_position_algebraic() {
+        let e4 = Position::from_algebraic("e4").unwrap();
+
+        assert_eq!((e4.rank(), e4.file()), (4, 4));
+        assert_eq!(e4.to_algebraic(), "e4");
+        assert_eq!(Position::create(0, 0).to_string(), "a8");
+        assert_eq!(Position::create(7, 7).to_string(), "h1");
+
+        for square in ["", "e", "e9", "e0", "i4", "E4", "e44"] {
+            assert_eq!(Position::from_algebraic(square), None);
         }
+    }
 
-        let mut valid_positions: Vec<Position>= Vec::new();
-        let castle_positions = get_castling_position(game);
+    #[test]
+    fn test_position_bounds() {
+        assert_eq!(Position::new(7, 0), Ok(Position::create(7, 0)));
+        assert_eq!(Position::new(8, 4), Err(ChessError::OUT_OF_BOUNDS(8, 4)));
+        assert_eq!(Position::new(4, 8), Err(ChessError::OUT_OF_BOUNDS(4, 8)));
+    }
 
-        for position in positions {
-            if game.board.get(position.rank, position.file) == '-' {
-                valid_positions.push(position)
+    #[test]
+    #[should_panic]
+    fn test_position_create_off_board() {
+        Position::create(8, 0);
+    }
+
+    #[test]
+    fn test_undo_restores_every_move() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 3 12",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 40",
+        ];
+
+        for fen in fens {
+            let mut game = Game::from_fen(fen).unwrap();
+
+            for chess_move in get_all_moves(&game) {
+                make_move(&mut game, &chess_move).unwrap();
+
+                assert_eq!(game.undo(), Some(chess_move));
+                assert_eq!(game.to_fen(), fen);
+                assert_eq!(game.status(), &Game::from_fen(fen).unwrap().status);
+                assert_eq!(game.position_history.len(), 1);
+                assert!(game.moves().is_empty());
             }
         }
+    }
 
-        for castle_position in castle_positions {
-            valid_positions.push(castle_position);
-        }
+    #[test]
+    fn test_undo_and_redo() {
+        let mut game = Game::new();
+        play(&mut game, &[(6, 5, 5, 5), (1, 4, 3, 4), (6, 6, 4, 6), (0, 3, 4, 7)]);
+
+        let mate_fen = game.to_fen();
+        assert_eq!(game.status(), &BLACK_HAS_CHECKMATE);
+        assert_eq!(game.moves().len(), 4);
+        assert_eq!(game.redo(), None);
+
+        let queen_move = game.undo().unwrap();
+        assert_eq!(queen_move.piece, Piece::new(QUEEN, BLACK));
+        assert_eq!(game.status(), &BLACK_TO_MOVE);
+        assert_eq!(game.current_move(), game.moves().last());
+
+        game.undo();
+        game.undo();
+        game.undo();
+        assert_eq!(game.undo(), None);
+        assert_eq!(game.to_fen(), Game::new().to_fen());
+        assert_eq!(game.current_move(), None);
+
+        while game.redo().is_some() {}
+        assert_eq!(game.to_fen(), mate_fen);
+        assert_eq!(game.status(), &BLACK_HAS_CHECKMATE);
+
+        // A new move after undo replaces the moves that could be redone
+        game.undo();
+        play(&mut game, &[(1, 3, 2, 3)]);
+        assert_eq!(game.redo(), None);
+        assert_eq!(game.moves().len(), 4);
+    }
 
-        return valid_positions;
+    #[test]
+    fn test_hash_is_updated_incrementally() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 40",
+        ];
+
+        for fen in fens {
+            let mut game = Game::from_fen(fen).unwrap();
+
+            for chess_move in get_all_moves(&game) {
+                make_move(&mut game, &chess_move).unwrap();
+                assert_eq!(game.hash(), zobrist::hash(&game));
+
+                for reply in get_all_moves(&game) {
+                    make_move(&mut game, &reply).unwrap();
+                    assert_eq!(game.hash(), Game::from_fen(&game.to_fen()).unwrap().hash());
+                    game.undo();
+                }
+
+                game.undo();
+                assert_eq!(game.hash(), zobrist::hash(&game));
+            }
+        }
     }
 
-    Vec::new()
-}
+    #[test]
+    fn test_hash_identifies_positions() {
+        // Fixed keys keep stored hashes valid
+        assert_eq!(Game::new().hash(), 14569182138830769793);
 
-pub fn get_rook_moves(board: &Board, rook_pos: &Position) -> Vec<Position> {
-    let piece = get_piece_from_position(&board, &rook_pos);
-    let mut positions = Vec::new();
+        let mut knights_first = Game::new();
+        play(&mut knights_first, &[(7, 6, 5, 5), (0, 6, 2, 5), (6, 4, 4, 4)]);
 
-    if piece == 'r' || piece == 'R' {
-        let horizontal_moves = get_horizontal_moves(&board, &rook_pos);
-        let vertical_moves = get_vertical_moves(&board, &rook_pos);
+        let mut pawn_first = Game::new();
+        play(&mut pawn_first, &[(6, 4, 4, 4), (0, 6, 2, 5), (7, 6, 5, 5)]);
 
-        for h_move in horizontal_moves {
-            positions.push(h_move);
-        }
+        assert_eq!(knights_first.hash(), pawn_first.hash());
 
-        for v_move in vertical_moves {
-            positions.push(v_move);
-        }
+        // Only the castling rights differ
+        let with_rights = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let without_rights = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kkq - 0 1").unwrap();
+        assert_ne!(with_rights.hash(), without_rights.hash());
 
-        return positions;
-    }
+        // The en passant file only counts when the capture is possible
+        let no_capture = Game::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").unwrap();
+        let no_target = Game::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(no_capture.hash(), no_target.hash());
 
-    positions
-}
+        let capture = Game::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        let too_late = Game::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1").unwrap();
+        assert_ne!(capture.hash(), too_late.hash());
 
-pub fn get_moves(game: &Game, position: &Position) -> Option<Vec<Position>> {
-    let square: char = game.board.get(position.rank, position.file);
+        let white_to_move = Game::from_fen("4k3/8/8/8/3pP3/8/8/4K3 w - - 0 1").unwrap();
+        assert_ne!(white_to_move.hash(), too_late.hash());
+    }
 
-    if (square != '-') {
-        let moves = match square.to_ascii_lowercase() {
-            'p' => get_pawn_moves(&game, &position),
-            'r' => get_rook_moves(&game.board, &position),
-            'n' => get_knight_moves(&game.board, &position),
-            'b' => get_bishop_moves(&game.board, &position),
-            'q' => get_queen_moves(&game.board, &position),
-            'k' => get_king_moves(&game, &position),
-            _ => Vec::new(),
-        };
+    #[test]
+    fn test_perft_positions() {
+        let positions = [
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3, 97862),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43238),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 3, 9467),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 3, 62379),
+        ];
+
+        for (fen, depth, expected) in positions {
+            let mut game = Game::from_fen(fen).unwrap();
+
+            assert_eq!(game.perft(depth), expected, "Perft of {} at depth {}", fen, depth);
+            assert_eq!(game.to_fen(), fen);
+        }
+    }
 
-        return Some(moves);
+    #[test]
+    fn test_perft_ignores_draw_rules() {
+        // Taking the last rook leaves king and knight against king, which is a draw but still has moves
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/3r4/1N2K3 w - - 0 1").unwrap();
+
+        make_move_with_promotion(&mut game, &Position::from_algebraic("b1").unwrap(), &Position::from_algebraic("d2").unwrap(), None).unwrap();
+        assert_eq!(game.status(), &DRAW(INSUFFICIENT_MATERIAL));
+        assert!(get_all_moves(&game).is_empty());
+        assert_eq!(game.perft(1), 5);
     }
 
-    None
-}
+    #[test]
+    fn test_perft_divide() {
+        let mut game = Game::new();
+        let counts = game.perft_divide(3);
+
+        assert_eq!(counts.len(), 20);
+        assert_eq!(counts.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+        assert!(counts.iter().any(|(chess_move, nodes)| chess_move.to_uci() == "e2e4" && *nodes == 600));
+        assert_eq!(game.perft_divide(1).iter().map(|(_, nodes)| nodes).sum::<u64>(), 20);
+    }
 
-pub fn get_all_moves(game: Game) -> HashMap<Position, Vec<Position>> {
-    let mut legal_moves: HashMap<Position, Vec<Position>> = HashMap::new();
+    #[test]
+    fn test_san_disambiguation() {
+        let game = Game::from_fen("8/8/6k1/8/8/Q7/8/Q1Q4K w - - 0 1").unwrap();
+        let b2 = Position::from_algebraic("b2").unwrap();
 
-    let mut row_index = 0;
+        let san = |from: &str| game.to_san(&find_move(&game, &Position::from_algebraic(from).unwrap(), &b2, None).unwrap()).unwrap();
 
-    for row in &game.board.board {
-        for (col, square) in row.iter().enumerate() {
-            if *square != '-' {
-                let piece = *square;
-                let position = Position::create(row_index, col);
+        assert_eq!(san("a1"), "Qa1b2");
+        assert_eq!(san("c1"), "Qcb2");
+        assert_eq!(san("a3"), "Q3b2");
 
-                let is_white = game.status == WHITE_TO_MOVE && piece.is_ascii_lowercase();
-                let is_black = game.status == BLACK_TO_MOVE && piece.is_ascii_uppercase();
+        let game = Game::from_fen("4k3/8/8/8/8/2N5/8/4K1N1 w - - 0 1").unwrap();
+        let san = |from: &str, to: &str| game.to_san(&find_move(&game, &Position::from_algebraic(from).unwrap(), &Position::from_algebraic(to).unwrap(), None).unwrap()).unwrap();
 
-                if is_white || is_black {
-                    let moves = get_moves(&game, &position).unwrap();
+        assert_eq!(san("g1", "e2"), "Nge2");
+        assert_eq!(san("c3", "e2"), "Nce2");
+        assert_eq!(san("c3", "d5"), "Nd5");
 
-                    legal_moves.insert(Position::create(row_index, col), moves);
-                }
-            }
-        }
+        let game = Game::from_fen("4k3/8/8/8/8/5N2/8/4KN2 w - - 0 1").unwrap();
+        let san = |from: &str, to: &str| game.to_san(&find_move(&game, &Position::from_algebraic(from).unwrap(), &Position::from_algebraic(to).unwrap(), None).unwrap()).unwrap();
 
-        row_index += 1;
+        assert_eq!(san("f1", "d2"), "N1d2");
+        assert_eq!(san("f3", "d2"), "N3d2");
     }
 
-    legal_moves
-}
+    #[test]
+    fn test_san_special_moves() {
+        let game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let sans: Vec<String> = get_all_moves(&game).iter().map(|chess_move| game.to_san(chess_move).unwrap()).collect();
 
-fn validate_moves(current_move: &Position, legal_moves: Vec<Position>) -> bool {
-    for legal_move in legal_moves {
-        if current_move.file == legal_move.file && current_move.rank == legal_move.rank {
-            return true;
-        }
-    }
+        assert!(sans.contains(&String::from("O-O")));
+        assert!(sans.contains(&String::from("O-O-O")));
+        assert!(sans.contains(&String::from("Rxa8+")));
 
-    false
-}
+        let game = Game::from_fen("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 40").unwrap();
+        let sans: Vec<String> = get_all_moves(&game).iter().map(|chess_move| game.to_san(chess_move).unwrap()).collect();
 
-pub fn make_move(game: &mut Game, start: &Position, end: &Position) -> Result<bool> {
-    let piece: char = game.board.get(start.rank, start.file);
+        assert!(sans.contains(&String::from("gxf1=Q+")));
+        assert!(sans.contains(&String::from("gxh1=N")));
+        assert!(sans.contains(&String::from("g1=R")));
 
-    if game.status == WHITE_TO_MOVE && piece.is_lowercase() {
-        return Err(Error::new(ErrorKind::Other, "Error: Cannot move black piece on white turn"))
-    }
+        let game = Game::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3").unwrap();
+        let en_passant = find_move(&game, &Position::from_algebraic("e5").unwrap(), &Position::from_algebraic("f6").unwrap(), None).unwrap();
+        assert_eq!(game.to_san(&en_passant).unwrap(), "exf6");
 
-    if game.status == BLACK_TO_MOVE && piece.is_uppercase() {
-        return Err(Error::new(ErrorKind::Other, "Error: Cannot move white piece on black turn"))
+        let e5_e6 = find_move(&game, &Position::from_algebraic("e5").unwrap(), &Position::from_algebraic("e6").unwrap(), None).unwrap();
+        assert_eq!(Game::new().to_san(&e5_e6), Err(ChessError::NO_PIECE_ON_SQUARE(Position::from_algebraic("e5").unwrap())));
     }
 
-    let positions = get_moves(game, start).unwrap();
+    #[test]
+    fn test_san_moves_of_game() {
+        let mut game = Game::new();
+        play(&mut game, &[(6, 5, 5, 5), (1, 4, 3, 4), (6, 6, 4, 6), (0, 3, 4, 7)]);
 
-    if validate_moves(end, positions) {
-        let isTargetEnemy = is_enemy(&game.board, start, end);
+        assert_eq!(game.san_moves(), ["f3", "e5", "g4", "Qh4#"]);
 
-        game.board.board[start.rank][start.file] = '-';
-        game.board.board[end.rank][end.file] = piece;
+        game.undo();
+        assert_eq!(game.san_moves(), ["f3", "e5", "g4"]);
+    }
 
-        if isTargetEnemy {
-            game.current_move = CAPTURE;
-        }
+    #[test]
+    fn test_parse_san() {
+        let mut game = Game::new();
 
-        else {
-            game.current_move = REGULAR;
+        for san in ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O", "f6"] {
+            let chess_move = game.parse_san(san).unwrap();
+            assert_eq!(game.to_san(&chess_move).unwrap(), san);
+            make_move(&mut game, &chess_move).unwrap();
         }
 
-        if game.status == WHITE_TO_MOVE {
-            game.status = BLACK_TO_MOVE;
-        }
+        assert_eq!(game.parse_san("d4").unwrap().to, Position::from_algebraic("d4").unwrap());
+        assert_eq!(game.parse_san("Qe1").unwrap().from, Position::from_algebraic("d1").unwrap());
+        assert_eq!(game.parse_san("Re1"), Ok(game.parse_san("Rfe1").unwrap()));
+        assert_eq!(game.parse_san("Nxe5!?").unwrap().captured, Piece::from_char('p'));
+        assert_eq!(game.parse_san("Nd5+"), Err(SanError::ILLEGAL_MOVE(String::from("Nd5+"))));
+        assert_eq!(game.parse_san("Nxd4"), Err(SanError::ILLEGAL_MOVE(String::from("Nxd4"))));
+        assert_eq!(game.parse_san("O-O"), Err(SanError::ILLEGAL_MOVE(String::from("O-O"))));
 
-        else if game.status == BLACK_TO_MOVE {
-            game.status = WHITE_TO_MOVE;
+        for san in ["", "x", "N", "Nz4", "e9", "Nbb1d2", "e4-e5", "exxd5", "O-O-O-O", "Kä1"] {
+            assert_eq!(game.parse_san(san), Err(SanError::MALFORMED(san.to_string())));
         }
+    }
 
-        return Ok(true);
+    #[test]
+    fn test_parse_san_variants_and_ambiguity() {
+        let game = Game::from_fen("r3k3/1P6/8/8/8/2N5/8/R3K1N1 w Qq - 0 1").unwrap();
+
+        assert_eq!(game.parse_san("0-0-0"), game.parse_san("O-O-O"));
+        assert_eq!(game.parse_san("0-0-0").unwrap().castle, Some(LONG));
+        assert_eq!(game.parse_san("Ne2"), Err(SanError::AMBIGUOUS_MOVE(String::from("Ne2"))));
+        assert_eq!(game.parse_san("Nge2").unwrap().from, Position::from_algebraic("g1").unwrap());
+        assert_eq!(game.parse_san("N1e2").unwrap().from, Position::from_algebraic("g1").unwrap());
+        assert_eq!(game.parse_san("Ng1e2").unwrap().from, Position::from_algebraic("g1").unwrap());
+
+        assert_eq!(game.parse_san("b8Q").unwrap().promotion, Piece::from_char('Q'));
+        assert_eq!(game.parse_san("bxa8=N+").unwrap().promotion, Piece::from_char('N'));
+        assert_eq!(game.parse_san("bxa8N").unwrap().captured, Piece::from_char('r'));
+        assert_eq!(game.parse_san("b8"), Err(SanError::ILLEGAL_MOVE(String::from("b8"))));
+        assert_eq!(game.parse_san("b8=K"), Err(SanError::MALFORMED(String::from("b8=K"))));
     }
 
-    Err(Error::new(ErrorKind::Other, "Error: Move is invalid"))
-}
+    #[test]
+    fn test_uci_notation() {
+        let mut game = Game::from_fen("r3k3/1P6/8/8/8/8/8/R3K2R w KQq - 0 1").unwrap();
 
-pub fn run() {
-    let mut game = Game::new();
+        let castle = game.parse_uci("e1g1").unwrap();
+        assert_eq!(castle.castle, Some(SHORT));
+        assert_eq!(castle.to_uci(), "e1g1");
 
-    loop {
-        game.board.print();
-        println!("");
+        let promotion = game.parse_uci("b7a8n").unwrap();
+        assert_eq!(promotion.promotion, Piece::from_char('N'));
+        assert_eq!(promotion.to_uci(), "b7a8n");
 
-        let mut input = String::new();
+        assert_eq!(game.parse_uci("b7b8"), Err(ChessError::PROMOTION_REQUIRED));
+        assert_eq!(game.parse_uci("b7b8k"), Err(ChessError::INVALID_UCI(String::from("b7b8k"))));
+        assert_eq!(game.parse_uci("e1e3"), Err(ChessError::ILLEGAL_MOVE));
+        assert_eq!(game.parse_uci("e8e7"), Err(ChessError::WRONG_TURN(WHITE)));
+
+        for uci in ["", "e2", "e2e", "e2e4qq", "e2e9", "i2e4", "E2E4", "0000", "e2e4Q"] {
+            assert_eq!(game.parse_uci(uci), Err(ChessError::INVALID_UCI(uci.to_string())));
+        }
 
-        println!("Enter position");
-        stdin().read_line(&mut input).unwrap();
+        for chess_move in get_all_moves(&game) {
+            assert_eq!(game.parse_uci(&chess_move.to_uci()), Ok(chess_move));
+        }
 
-        let nums: Vec<usize> = input.split_whitespace().map(|w| w.parse().unwrap()).collect();
-        let startRow = nums[0];
-        let startFile = nums[1];
+        game.make_uci_move("b7b8q").unwrap();
+        game.make_uci_move("e8e7").unwrap();
+        assert_eq!(game.to_fen(), "rQ6/4k3/8/8/8/8/8/R3K2R w KQ - 1 2");
+    }
 
-        let endRow = nums[2];
-        let endFile = nums[3];
+    #[test]
+    fn test_pgn_export() {
+        let mut game = Game::new();
+        game.set_tag("Event", "Club \"Blitz\" night");
+        game.set_tag("White", "Anna");
+        game.set_tag("White", "Ben");
+        game.set_tag("Annotator", "Carla");
+        game.set_tag("Result", "1-0");
+        play(&mut game, &[(6, 5, 5, 5), (1, 4, 3, 4), (6, 6, 4, 6), (0, 3, 4, 7)]);
+
+        assert_eq!(game.tag("White"), Some("Ben"));
+        assert_eq!(game.tag("Black"), None);
+        assert_eq!(game.to_pgn(), [
+            "[Event \"Club \\\"Blitz\\\" night\"]",
+            "[Site \"?\"]",
+            "[Date \"????.??.??\"]",
+            "[Round \"?\"]",
+            "[White \"Ben\"]",
+            "[Black \"?\"]",
+            "[Result \"0-1\"]",
+            "[Annotator \"Carla\"]",
+            "",
+            "1. f3 e5 2. g4 Qh4# 0-1",
+            "",
+        ].join("\n"));
+
+        // Without the mate the result that was set is kept, like after a resignation
+        game.undo();
+        assert!(game.to_pgn().contains("[Result \"1-0\"]\n"));
+        assert!(game.to_pgn().ends_with("\n1. f3 e5 2. g4 1-0\n"));
+
+        game.set_tag("Result", "*");
+        assert_eq!(game.result(), "*");
+        assert_eq!(Game::new().result(), "*");
+    }
 
-        match make_move(&mut game, &Position::create(startRow, startFile), &Position::create(endRow, endFile)) {
-            Ok(v) => println!("Success"),
-            Err(e) => println!("{}", e)
+    #[test]
+    fn test_pgn_export_from_position() {
+        let fen = "4k3/8/8/8/8/8/8/R3K3 b Q - 0 30";
+        let mut game = Game::from_fen(fen).unwrap();
+        play(&mut game, &[(0, 4, 0, 3), (7, 4, 7, 2), (0, 3, 0, 4)]);
+
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 0 30\"]\n"));
+        assert!(pgn.ends_with("\n30... Kd8 31. O-O-O+ Ke8 *\n"));
+        assert!(!Game::new().to_pgn().contains("FEN"));
+
+        // A long game is wrapped without splitting a token
+        let mut game = Game::new();
+        let moves = concat!(
+            "a3 a6 b3 b6 c3 c6 d3 d6 e3 e6 f3 f6 g3 g6 h3 h6 a4 a5 b4 b5 c4 c5 d4 d5 e4 e5 ",
+            "f4 f5 g4 g5 h4 h5 Nc3 Nc6 Nf3 Nf6 Bb2 Bb7 Bg2 Bg7 Qd2 Qd7 O-O O-O Rfe1 Rfe8 Rad1 Rad8 Kh2 Kh7",
+        );
+
+        for san in moves.split_whitespace() {
+            let chess_move = game.parse_san(san).unwrap();
+            make_move(&mut game, &chess_move).unwrap();
         }
+
+        let pgn = game.to_pgn();
+        let movetext: Vec<&str> = pgn.split("\n\n").nth(1).unwrap().lines().collect();
+
+        assert!(movetext.len() > 1);
+        assert!(movetext.iter().all(|line| line.len() <= 80 && !line.starts_with(' ') && !line.ends_with(' ')));
+        assert_eq!(movetext.join(" ").split(' ').count(), 25 + 50 + 1);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_read_pgn() {
+        let pgn = concat!(
+            "[Event \"Casual \\\"game\\\"\"]\n",
+            "[White \"Anna\"]\n",
+            "[Black \"Ben\"]\n",
+            "\n",
+            "{Opening} 1. e4 e5 $1 2. Nf3 {Main line} (2. f4!? exf4 (2... d5) 3. Nf3) 2... Nc6 ; rest of line\n",
+            "3.Bb5 a6 1-0\n",
+            "\n",
+            "[Event \"Second\"]\n",
+            "[SetUp \"1\"]\n",
+            "[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 0 30\"]\n",
+            "\n",
+            "30... Kd8 31. O-O-O+ Ke8 *\n",
+        );
+
+        let games = read_pgn(pgn).unwrap();
+        assert_eq!(games.len(), 2);
+
+        let first = &games[0];
+        assert_eq!(first.game.tag("Event"), Some("Casual \"game\""));
+        assert_eq!(first.game.tag("Result"), None);
+        assert_eq!(first.result, "1-0");
+        assert_eq!(first.game.san_moves(), ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]);
+        assert_eq!(first.moves.len(), 6);
+        assert_eq!(first.moves[0].comments_before, ["Opening"]);
+        assert_eq!(first.moves[1].nags, [1]);
+        assert_eq!(first.moves[2].comments, ["Main line"]);
+        assert_eq!(first.moves[3].comments, ["rest of line"]);
+
+        let variation = &first.moves[2].variations[0];
+        assert_eq!(variation.len(), 3);
+        assert_eq!(variation[0].chess_move.to_uci(), "f2f4");
+        assert_eq!(variation[0].nags, [5]);
+        assert_eq!(variation[1].variations[0][0].chess_move.to_uci(), "d7d5");
+
+        let second = &games[1];
+        assert_eq!(second.game.tag("FEN"), Some("4k3/8/8/8/8/8/8/R3K3 b Q - 0 30"));
+        assert_eq!(second.game.san_moves(), ["Kd8", "O-O-O+", "Ke8"]);
+        assert_eq!(second.result, "*");
+        assert!(second.game.to_pgn().ends_with("\n30... Kd8 31. O-O-O+ Ke8 *\n"));
+
+        assert!(read_pgn("").unwrap().is_empty());
+        assert_eq!(read_pgn("1. e4 e5 2. Nf3").unwrap()[0].moves.len(), 3);
+        assert_eq!(read_pgn("1. e4 e5 2. Nf3").unwrap()[0].result, "*");
+        assert_eq!(read_pgn("1. d4 1/2-1/2\n1. e4 0-1").unwrap().iter().map(|game| game.result.as_str()).collect::<Vec<&str>>(), ["1/2-1/2", "0-1"]);
+    }
 
     #[test]
-    fn test_perft_depth_1() {
-        let board = Board::create();
-        let nodes = board.perft(1, WHITE);
-        assert_eq!(nodes, 20, "Perft Depth 1 failed: Expected 20 nodes, got {}", nodes);
+    fn test_read_pgn_errors() {
+        let error = |pgn: &str| read_pgn(pgn).err().unwrap();
+
+        let illegal = error("[Event \"?\"]\n\n1. e4 e5\n2. Ke3 Nc6 *");
+        assert_eq!((illegal.line, illegal.column), (4, 4));
+        assert_eq!(illegal.kind, PgnErrorKind::ILLEGAL_MOVE(SanError::ILLEGAL_MOVE(String::from("Ke3"))));
+        assert_eq!(illegal.to_string(), "Error: Line 4, column 4: Ke3 is not a legal move");
+
+        // Moves in a variation are played from the position before the move they replace
+        let in_variation = error("1. e4 e5 (1... Nf3) *");
+        assert_eq!((in_variation.line, in_variation.column), (1, 16));
+
+        let unclosed = error("1. e4 (1. d4 d5\n2. c4 *");
+        assert_eq!((unclosed.line, unclosed.column, unclosed.kind), (1, 7, PgnErrorKind::UNTERMINATED_VARIATION));
+
+        assert_eq!(error("1. e4 {never closed").kind, PgnErrorKind::UNTERMINATED_COMMENT);
+        assert_eq!(error("[Event Club]\n1. e4 *").kind, PgnErrorKind::INVALID_TAG);
+        assert_eq!(error("1. e4 ) *").kind, PgnErrorKind::UNEXPECTED_TOKEN(String::from(")")));
+        assert_eq!(error("1. e4 & *").kind, PgnErrorKind::UNEXPECTED_CHARACTER('&'));
+        assert_eq!(error("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n*").kind, PgnErrorKind::INVALID_FEN(FenError::WRONG_KING_COUNT(WHITE, 0)));
     }
 
     #[test]
-    fn test_perft_depth_2() {
-        let board = Board::create();
-        let nodes = board.perft(2, WHITE);
-        assert_eq!(nodes, 400, "Perft Depth 2 failed: Expected 400 nodes, got {}", nodes);
+    fn test_game_tree_pgn_round_trip() {
+        let movetext = "{Opening} 1. e4 e5 $1 2. Nf3 {Main line} (2. f4 $5 exf4 (2... d5) 3. Nf3) 2... Nc6 {rest of line} 3. Bb5 a6 *";
+        let pgn = format!("[Event \"Club\"]\n\n{}\n", movetext);
+
+        let tree = GameTree::from_pgn(&read_pgn(&pgn).unwrap()[0]).unwrap();
+        let main_line = tree.main_line();
+        assert_eq!(main_line.len(), 6);
+
+        let after_e5 = tree.node(main_line[1]).unwrap();
+        assert_eq!(after_e5.nags, [1]);
+        assert_eq!(after_e5.children().len(), 2);
+
+        let gambit = tree.node(after_e5.children()[1]).unwrap();
+        assert_eq!(gambit.chess_move().unwrap().to_uci(), "f2f4");
+        assert_eq!(gambit.game().san_moves(), ["e4", "e5", "f4"]);
+
+        let written = tree.to_pgn();
+        assert!(written.starts_with("[Event \"Club\"]\n"));
+        assert_eq!(written.split("\n\n").nth(1).unwrap().replace('\n', " "), format!("{} ", movetext));
+        assert_eq!(GameTree::from_pgn(&read_pgn(&written).unwrap()[0]).unwrap().to_pgn(), written);
+
+        // A decisive result is kept even though the game is not over on the board
+        let resigned = GameTree::from_pgn(&read_pgn("1. e4 e5 (1... c5) 2. Qh5 1-0").unwrap()[0]).unwrap();
+        assert!(resigned.to_pgn().contains("[Result \"1-0\"]\n"));
+        assert!(resigned.to_pgn().ends_with("\n1. e4 e5 (1... c5) 2. Qh5 1-0\n"));
+
+        let mut illegal = read_pgn("1. e4 e5 *").unwrap().remove(0);
+        illegal.moves[1].chess_move = illegal.moves[0].chess_move;
+        assert_eq!(GameTree::from_pgn(&illegal).err(), Some(ChessError::NO_PIECE_ON_SQUARE(Position::from_algebraic("e2").unwrap())));
     }
 
     #[test]
-    fn test_perft_depth_3() {
-        let board = Board::create();
-        let nodes = board.perft(3, WHITE);
-        assert_eq!(nodes, 8902, "Perft Depth 3 failed: Expected 8902 nodes, got {}", nodes);
+    fn test_game_tree_editing() {
+        let mut tree = GameTree::new(Game::new());
+        let root = tree.root();
+        let uci = |tree: &GameTree, id: NodeId| tree.node(id).unwrap().chess_move().unwrap().to_uci();
+
+        let e4 = tree.add_move(root, &Game::new().parse_uci("e2e4").unwrap()).unwrap();
+        let e5 = tree.add_move(e4, &tree.node(e4).unwrap().game().parse_san("e5").unwrap()).unwrap();
+        let c5 = tree.add_move(e4, &tree.node(e4).unwrap().game().parse_san("c5").unwrap()).unwrap();
+        let nf3 = tree.add_move(c5, &tree.node(c5).unwrap().game().parse_san("Nf3").unwrap()).unwrap();
+
+        // Playing a move that is already in the tree goes to its node
+        assert_eq!(tree.add_move(root, &Game::new().parse_uci("e2e4").unwrap()), Ok(e4));
+        assert_eq!(tree.add_move(e5, &Game::new().parse_uci("e2e4").unwrap()), Err(ChessError::NO_PIECE_ON_SQUARE(Position::from_algebraic("e2").unwrap())));
+        assert_eq!(tree.add_move(99, &Game::new().parse_uci("e2e4").unwrap()), Err(ChessError::UNKNOWN_NODE(99)));
+
+        assert_eq!(tree.main_line(), [e4, e5]);
+        assert_eq!(tree.path(nf3), [root, e4, c5, nf3]);
+        assert_eq!(tree.node(nf3).unwrap().parent(), Some(c5));
+
+        assert!(tree.promote(c5));
+        assert!(!tree.promote(c5));
+        assert_eq!(tree.main_line(), [e4, c5, nf3]);
+
+        assert!(tree.demote(c5));
+        assert!(!tree.demote(c5));
+        assert_eq!(tree.main_line(), [e4, e5]);
+
+        tree.make_main_line(nf3);
+        assert_eq!(tree.main_line().iter().map(|id| uci(&tree, *id)).collect::<Vec<String>>(), ["e2e4", "c7c5", "g1f3"]);
+
+        assert!(tree.delete(c5));
+        assert!(!tree.delete(root));
+        assert!(tree.node(nf3).is_none());
+        assert_eq!(tree.main_line(), [e4, e5]);
+        assert!(tree.to_pgn().ends_with("\n1. e4 e5 *\n"));
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,3 @@
-use arday11ChessLibrary;
 use arday11ChessLibrary::run;
 
 fn main() {
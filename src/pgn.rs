@@ -0,0 +1,468 @@
+use std::iter::Peekable;
+use std::str::Chars;
+use crate::{make_move, Game, Move, PgnError, PgnErrorKind, SanError, STARTING_FEN};
+use crate::Color::BLACK;
+use crate::Status::{BLACK_HAS_CHECKMATE, DRAW, STALEMATE, WHITE_HAS_CHECKMATE};
+
+// The Seven Tag Roster with the values used when a tag was never set
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+// Tags that are always written from the game itself
+const GENERATED_TAGS: [&str; 3] = ["Result", "SetUp", "FEN"];
+
+const LINE_WIDTH: usize = 80;
+
+impl Game {
+    /// Sets a PGN tag like "Event", "White" or "Date", replacing an earlier value
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old_value)) => *old_value = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    /// The result token of the game: "1-0", "0-1", "1/2-1/2" or "*". A game that is over gets the
+    /// result of its status, otherwise a "Result" tag set for a resignation, timeout or agreed draw
+    /// is used and "*" while the game goes on.
+    pub fn result(&self) -> &str {
+        match self.status {
+            WHITE_HAS_CHECKMATE => "1-0",
+            BLACK_HAS_CHECKMATE => "0-1",
+            DRAW(_) | STALEMATE => "1/2-1/2",
+            _ => match self.tag("Result") {
+                Some(result @ ("1-0" | "0-1" | "1/2-1/2")) => result,
+                _ => "*",
+            },
+        }
+    }
+
+    /// Writes the game as PGN. The Seven Tag Roster is always written, with "?" for tags that were
+    /// not set, followed by the other tags. Games that did not start from the usual position get
+    /// SetUp and FEN tags. The moves are written in SAN and the lines are at most 80 characters long.
+    pub fn to_pgn(&self) -> String {
+        let mut start = self.clone();
+        while start.unmake_move().is_some() {}
+
+        let mut tokens: Vec<String> = Vec::new();
+        let mut number = start.fullmove_number;
+        let mut color = start.side_to_move;
+
+        for (index, san) in self.san_moves().into_iter().enumerate() {
+            if color == BLACK && index == 0 {
+                tokens.push(format!("{}...", number));
+            }
+
+            else if color != BLACK {
+                tokens.push(format!("{}.", number));
+            }
+
+            tokens.push(san);
+
+            if color == BLACK {
+                number += 1;
+            }
+
+            color = color.opposite();
+        }
+
+        tokens.push(self.result().to_string());
+
+        write_pgn(self, &start, self.result(), &tokens)
+    }
+}
+
+// Puts the tags of the game and the movetext tokens together. The start is the position the
+// movetext starts from.
+pub(crate) fn write_pgn(game: &Game, start: &Game, result: &str, tokens: &[String]) -> String {
+    let start_fen = start.to_fen();
+    let mut pgn = String::new();
+
+    for (name, default) in SEVEN_TAG_ROSTER {
+        let value = if name == "Result" { result } else { game.tag(name).unwrap_or(default) };
+        pgn.push_str(&tag_pair(name, value));
+    }
+
+    if start_fen != format!("{} w KQkq - 0 1", STARTING_FEN) {
+        pgn.push_str(&tag_pair("SetUp", "1"));
+        pgn.push_str(&tag_pair("FEN", &start_fen));
+    }
+
+    for (name, value) in &game.tags {
+        if !SEVEN_TAG_ROSTER.iter().any(|(roster_name, _)| roster_name == name) && !GENERATED_TAGS.contains(&name.as_str()) {
+            pgn.push_str(&tag_pair(name, value));
+        }
+    }
+
+    pgn.push('\n');
+    pgn.push_str(&wrap(tokens));
+    pgn.push('\n');
+    pgn
+}
+
+fn tag_pair(name: &str, value: &str) -> String {
+    format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Joins the tokens with spaces and starts a new line before a token that would not fit
+fn wrap(tokens: &[String]) -> String {
+    let mut text = String::new();
+    let mut line_length = 0;
+
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > LINE_WIDTH {
+            text.push('\n');
+            line_length = 0;
+        }
+
+        else if line_length > 0 {
+            text.push(' ');
+            line_length += 1;
+        }
+
+        text.push_str(token);
+        line_length += token.len();
+    }
+
+    text
+}
+
+/// A game read from PGN. The game holds the tags and the main line, the moves keep the comments,
+/// NAGs and variations written next to them.
+#[derive(Clone)]
+pub struct PgnGame {
+    pub game: Game,
+    pub moves: Vec<PgnMove>,
+    // The result written at the end of the movetext, "*" when it was left out
+    pub result: String,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct PgnMove {
+    pub chess_move: Move,
+    // Numeric Annotation Glyphs, where "!" and "?" suffixes are stored as $1 to $6
+    pub nags: Vec<u8>,
+    // Comments written before the move when it starts a line, and after the move
+    pub comments_before: Vec<String>,
+    pub comments: Vec<String>,
+    // Lines that could have been played instead of this move
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+#[derive(PartialEq, Debug)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    OpenVariation,
+    CloseVariation,
+    MoveNumber,
+    San(String),
+    Result(String),
+}
+
+// A token with the line and column it starts at
+type Located = (Token, usize, usize);
+
+/// Reads every game of a PGN file. Each main line is replayed with `make_move`, so a move that
+/// is illegal or can't be read stops the import with the line and column of the move.
+pub fn read_pgn(pgn: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let tokens = tokenize(pgn)?;
+    let mut games: Vec<PgnGame> = Vec::new();
+    let mut index = 0;
+
+    while index < tokens.len() {
+        games.push(read_game(&tokens, &mut index)?);
+    }
+
+    Ok(games)
+}
+
+fn error(line: usize, column: usize, kind: PgnErrorKind) -> PgnError {
+    PgnError {
+        line,
+        column,
+        kind
+    }
+}
+
+// Walks over the text and keeps track of the line and column of the next character
+struct Scanner<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl Scanner<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let character = self.chars.next()?;
+
+        if character == '\n' {
+            self.line += 1;
+            self.column = 1;
+        }
+
+        else {
+            self.column += 1;
+        }
+
+        Some(character)
+    }
+
+    fn take_while(&mut self, condition: impl Fn(char) -> bool) -> String {
+        let mut text = String::new();
+
+        while let Some(character) = self.peek().filter(|character| condition(*character)) {
+            text.push(character);
+            self.next();
+        }
+
+        text
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+}
+
+fn is_symbol_character(character: char) -> bool {
+    character.is_ascii_alphanumeric() || "+#=:-/_".contains(character)
+}
+
+fn tokenize(pgn: &str) -> Result<Vec<Located>, PgnError> {
+    let mut scanner = Scanner {
+        chars: pgn.chars().peekable(),
+        line: 1,
+        column: 1,
+    };
+
+    let mut tokens: Vec<Located> = Vec::new();
+
+    loop {
+        scanner.skip_whitespace();
+
+        let (line, column) = (scanner.line, scanner.column);
+
+        let character = match scanner.peek() {
+            Some(character) => character,
+            None => return Ok(tokens),
+        };
+
+        let token = match character {
+            '[' => read_tag(&mut scanner).ok_or(error(line, column, PgnErrorKind::INVALID_TAG))?,
+            '{' => {
+                scanner.next();
+                let comment = scanner.take_while(|character| character != '}');
+
+                if scanner.next().is_none() {
+                    return Err(error(line, column, PgnErrorKind::UNTERMINATED_COMMENT));
+                }
+
+                Token::Comment(comment.trim().to_string())
+            }
+            ';' => Token::Comment(scanner.take_while(|character| character != '\n')[1..].trim().to_string()),
+            '%' if column == 1 => {
+                scanner.take_while(|character| character != '\n');
+                continue;
+            }
+            '(' => {
+                scanner.next();
+                Token::OpenVariation
+            }
+            ')' => {
+                scanner.next();
+                Token::CloseVariation
+            }
+            '*' => {
+                scanner.next();
+                Token::Result(String::from("*"))
+            }
+            '.' => {
+                scanner.take_while(|character| character == '.');
+                Token::MoveNumber
+            }
+            '$' => {
+                scanner.next();
+
+                match scanner.take_while(|character| character.is_ascii_digit()).parse() {
+                    Ok(nag) => Token::Nag(nag),
+                    Err(_) => return Err(error(line, column, PgnErrorKind::UNEXPECTED_CHARACTER('$'))),
+                }
+            }
+            '!' | '?' => match scanner.take_while(|character| character == '!' || character == '?').as_str() {
+                "!" => Token::Nag(1),
+                "?" => Token::Nag(2),
+                "!!" => Token::Nag(3),
+                "??" => Token::Nag(4),
+                "!?" => Token::Nag(5),
+                "?!" => Token::Nag(6),
+                _ => return Err(error(line, column, PgnErrorKind::UNEXPECTED_CHARACTER(character))),
+            },
+            _ if is_symbol_character(character) => {
+                let symbol = scanner.take_while(is_symbol_character);
+
+                if symbol.chars().all(|character| character.is_ascii_digit()) {
+                    scanner.take_while(|character| character == '.');
+                    Token::MoveNumber
+                }
+
+                else if ["1-0", "0-1", "1/2-1/2"].contains(&symbol.as_str()) {
+                    Token::Result(symbol)
+                }
+
+                else {
+                    Token::San(symbol)
+                }
+            }
+            _ => return Err(error(line, column, PgnErrorKind::UNEXPECTED_CHARACTER(character))),
+        };
+
+        tokens.push((token, line, column));
+    }
+}
+
+// Reads a tag pair like [Event "Club championship"], where the value may escape quotes and backslashes
+fn read_tag(scanner: &mut Scanner) -> Option<Token> {
+    scanner.next();
+    scanner.skip_whitespace();
+
+    let name = scanner.take_while(|character| character.is_ascii_alphanumeric() || character == '_');
+    scanner.skip_whitespace();
+
+    if name.is_empty() || scanner.next()? != '"' {
+        return None;
+    }
+
+    let mut value = String::new();
+
+    loop {
+        match scanner.next()? {
+            '"' => break,
+            '\\' => value.push(scanner.next()?),
+            '\n' => return None,
+            character => value.push(character),
+        }
+    }
+
+    scanner.skip_whitespace();
+
+    if scanner.next()? != ']' {
+        return None;
+    }
+
+    Some(Token::Tag(name, value))
+}
+
+// Reads the tags and the movetext of one game, up to and including its result
+fn read_game(tokens: &[Located], index: &mut usize) -> Result<PgnGame, PgnError> {
+    let mut game = Game::new();
+    let mut tags: Vec<(String, String)> = Vec::new();
+
+    while let Some((Token::Tag(name, value), line, column)) = tokens.get(*index) {
+        if name == "FEN" {
+            game = Game::from_fen(value).map_err(|fen_error| error(*line, *column, PgnErrorKind::INVALID_FEN(fen_error)))?;
+        }
+
+        tags.push((name.clone(), value.clone()));
+        *index += 1;
+    }
+
+    for (name, value) in tags {
+        game.set_tag(&name, &value);
+    }
+
+    let moves = read_line(tokens, index, &mut game, None)?;
+
+    let result = match tokens.get(*index) {
+        Some((Token::Result(result), _, _)) => {
+            *index += 1;
+            result.clone()
+        }
+        _ => String::from("*"),
+    };
+
+    Ok(PgnGame {
+        game,
+        moves,
+        result
+    })
+}
+
+// Reads moves until the end of the line and plays them in the game. A variation ends with a
+// closing parenthesis, the main line before the result, the tags of the next game or the end of
+// the file. For a variation the position of its opening parenthesis is given.
+fn read_line(tokens: &[Located], index: &mut usize, game: &mut Game, variation_start: Option<(usize, usize)>) -> Result<Vec<PgnMove>, PgnError> {
+    let mut moves: Vec<PgnMove> = Vec::new();
+    let mut leading_comments: Vec<String> = Vec::new();
+
+    while let Some((token, line, column)) = tokens.get(*index) {
+        *index += 1;
+
+        match (token, variation_start) {
+            (Token::Tag(..) | Token::Result(_), None) => {
+                *index -= 1;
+                return Ok(moves);
+            }
+            (Token::CloseVariation, Some(_)) => return Ok(moves),
+            (Token::Tag(..) | Token::Result(_), Some((line, column))) => {
+                return Err(error(line, column, PgnErrorKind::UNTERMINATED_VARIATION));
+            }
+            (Token::CloseVariation, None) => return Err(error(*line, *column, PgnErrorKind::UNEXPECTED_TOKEN(String::from(")")))),
+            (Token::MoveNumber, _) => {}
+            (Token::Comment(comment), _) => match moves.last_mut() {
+                Some(last) => last.comments.push(comment.clone()),
+                None => leading_comments.push(comment.clone()),
+            },
+            (Token::Nag(nag), _) => match moves.last_mut() {
+                Some(last) => last.nags.push(*nag),
+                None => return Err(error(*line, *column, PgnErrorKind::UNEXPECTED_TOKEN(format!("${} before the first move", nag)))),
+            },
+            (Token::OpenVariation, _) => {
+                let last = match moves.last_mut() {
+                    Some(last) => last,
+                    None => return Err(error(*line, *column, PgnErrorKind::UNEXPECTED_TOKEN(String::from("( before the first move")))),
+                };
+
+                let mut branch = game.clone();
+                branch.undo();
+
+                let variation = read_line(tokens, index, &mut branch, Some((*line, *column)))?;
+                last.variations.push(variation);
+            }
+            (Token::San(san), _) => {
+                let illegal = |san_error| error(*line, *column, PgnErrorKind::ILLEGAL_MOVE(san_error));
+                let chess_move = game.parse_san(san).map_err(illegal)?;
+
+                make_move(game, &chess_move).map_err(|_| illegal(SanError::ILLEGAL_MOVE(san.clone())))?;
+
+                moves.push(PgnMove {
+                    chess_move,
+                    nags: Vec::new(),
+                    comments_before: std::mem::take(&mut leading_comments),
+                    comments: Vec::new(),
+                    variations: Vec::new(),
+                });
+            }
+        }
+    }
+
+    match variation_start {
+        Some((line, column)) => Err(error(line, column, PgnErrorKind::UNTERMINATED_VARIATION)),
+        None => Ok(moves),
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{apply_move, find_move, get_all_moves, ChessError, Game, Move, PieceKind, Position, SanError};
+use crate::CastleSide::{LONG, SHORT};
+use crate::PieceKind::PAWN;
+use crate::Status::{BLACK_HAS_CHECKMATE, WHITE_HAS_CHECKMATE};
+
+impl Game {
+    /// Writes a legal move in Standard Algebraic Notation, like "Nbd7", "exd5", "O-O" or "e8=Q#"
+    pub fn to_san(&self, chess_move: &Move) -> Result<String, ChessError> {
+        let chess_move = find_move(self, &chess_move.from, &chess_move.to, chess_move.promotion.map(|piece| piece.kind))?;
+        let mut san = describe_move(self, &chess_move);
+
+        let mut after = self.clone();
+        apply_move(&mut after, chess_move);
+        san.push_str(check_suffix(&after));
+
+        Ok(san)
+    }
+
+    /// Finds the legal move written in Standard Algebraic Notation. Check and mate signs and
+    /// annotations like "!?" may be left out, castling can also be written with zeros and the
+    /// "=" of a promotion is optional.
+    pub fn parse_san(&self, san: &str) -> Result<Move, SanError> {
+        let text = san.trim().trim_end_matches(['+', '#', '!', '?']);
+
+        let castle = match text {
+            "O-O" | "0-0" => Some(SHORT),
+            "O-O-O" | "0-0-0" => Some(LONG),
+            _ => None,
+        };
+
+        let candidates: Vec<Move> = match castle {
+            Some(side) => get_all_moves(self).into_iter().filter(|chess_move| chess_move.castle == Some(side)).collect(),
+            None => {
+                let pattern = SanPattern::parse(text).ok_or_else(|| SanError::MALFORMED(san.to_string()))?;
+
+                get_all_moves(self).into_iter().filter(|chess_move| pattern.matches(chess_move)).collect()
+            }
+        };
+
+        match candidates.as_slice() {
+            [] => Err(SanError::ILLEGAL_MOVE(san.to_string())),
+            [chess_move] => Ok(*chess_move),
+            _ => Err(SanError::AMBIGUOUS_MOVE(san.to_string())),
+        }
+    }
+
+    /// The moves played so far in Standard Algebraic Notation
+    pub fn san_moves(&self) -> Vec<String> {
+        let mut game = self.clone();
+        let mut moves: Vec<String> = Vec::new();
+
+        while game.unmake_move().is_some() {}
+
+        for chess_move in &self.moves {
+            let mut san = describe_move(&game, chess_move);
+
+            apply_move(&mut game, *chess_move);
+            san.push_str(check_suffix(&game));
+            moves.push(san);
+        }
+
+        moves
+    }
+}
+
+// The SAN of a move without the check or mate suffix
+fn describe_move(game: &Game, chess_move: &Move) -> String {
+    match chess_move.castle {
+        Some(SHORT) => return String::from("O-O"),
+        Some(_) => return String::from("O-O-O"),
+        None => {}
+    }
+
+    let mut san = String::new();
+
+    if chess_move.piece.kind == PAWN {
+        if chess_move.is_capture() {
+            san.push_str(&chess_move.from.to_algebraic()[..1]);
+        }
+    }
+
+    else {
+        san.push(chess_move.piece.kind.to_char());
+        san.push_str(&disambiguation(game, chess_move));
+    }
+
+    if chess_move.is_capture() {
+        san.push('x');
+    }
+
+    san.push_str(&chess_move.to.to_algebraic());
+
+    if let Some(promotion) = chess_move.promotion {
+        san.push('=');
+        san.push(promotion.kind.to_char());
+    }
+
+    san
+}
+
+// The file, the rank or the whole square of the moving piece, whatever is needed to tell it
+// apart from other pieces of the same kind that can move to the same square
+fn disambiguation(game: &Game, chess_move: &Move) -> String {
+    let rivals: Vec<Move> = get_all_moves(game)
+        .into_iter()
+        .filter(|other| other.piece == chess_move.piece && other.to == chess_move.to && other.from != chess_move.from)
+        .collect();
+
+    let from = chess_move.from.to_algebraic();
+
+    if rivals.is_empty() {
+        String::new()
+    } else if rivals.iter().all(|other| other.from.file() != chess_move.from.file()) {
+        from[..1].to_string()
+    } else if rivals.iter().all(|other| other.from.rank() != chess_move.from.rank()) {
+        from[1..].to_string()
+    } else {
+        from
+    }
+}
+
+fn check_suffix(game: &Game) -> &'static str {
+    if *game.status() == WHITE_HAS_CHECKMATE || *game.status() == BLACK_HAS_CHECKMATE {
+        "#"
+    } else if game.in_check() {
+        "+"
+    } else {
+        ""
+    }
+}
+
+// What a SAN string other than castling says about a move
+struct SanPattern {
+    kind: PieceKind,
+    from_file: Option<usize>,
+    from_rank: Option<usize>,
+    capture: bool,
+    to: Position,
+    promotion: Option<PieceKind>,
+}
+
+impl SanPattern {
+    // Splits a move like "Nbxd7" or "e8=Q" into its parts, from the piece letter at the start
+    // to the promotion piece at the end
+    fn parse(text: &str) -> Option<SanPattern> {
+        if !text.is_ascii() {
+            return None;
+        }
+
+        let (kind, rest) = match text.chars().next()? {
+            symbol @ ('N' | 'B' | 'R' | 'Q' | 'K') => (PieceKind::from_char(symbol)?, &text[1..]),
+            _ => (PAWN, text),
+        };
+
+        let (rest, promotion) = match rest.chars().last()? {
+            symbol @ ('N' | 'B' | 'R' | 'Q') if kind == PAWN => {
+                let rest = &rest[..rest.len() - 1];
+                (rest.strip_suffix('=').unwrap_or(rest), PieceKind::from_char(symbol))
+            }
+            _ => (rest, None),
+        };
+
+        if rest.len() < 2 {
+            return None;
+        }
+
+        let to = Position::from_algebraic(&rest[rest.len() - 2..])?;
+        let prefix = &rest[..rest.len() - 2];
+        let (from, capture) = match prefix.strip_suffix(['x', ':']) {
+            Some(from) => (from, true),
+            None => (prefix, false),
+        };
+
+        let mut from = from.chars().peekable();
+
+        let from_file = from.next_if(|symbol| ('a'..='h').contains(symbol)).map(|symbol| symbol as usize - 'a' as usize);
+        let from_rank = from.next_if(|symbol| ('1'..='8').contains(symbol)).map(|symbol| 8 - (symbol as usize - '0' as usize));
+
+        if from.next().is_some() {
+            return None;
+        }
+
+        Some(SanPattern {
+            kind,
+            from_file,
+            from_rank,
+            capture,
+            to,
+            promotion,
+        })
+    }
+
+    fn matches(&self, chess_move: &Move) -> bool {
+        chess_move.piece.kind == self.kind
+            && chess_move.to == self.to
+            && !chess_move.is_castle()
+            && chess_move.promotion.map(|piece| piece.kind) == self.promotion
+            && self.from_file.is_none_or(|file| chess_move.from.file() == file)
+            && self.from_rank.is_none_or(|rank| chess_move.from.rank() == rank)
+            && (!self.capture || chess_move.is_capture())
+    }
+}
@@ -0,0 +1,337 @@
+use crate::{make_move, ChessError, Game, Move, PgnGame, PgnMove};
+use crate::Color::WHITE;
+use crate::pgn::write_pgn;
+
+/// Identifies a node of a `GameTree`. Ids stay the same while the tree changes, the id of a
+/// deleted node is never used again.
+pub type NodeId = usize;
+
+/// A position in a game tree with the move that led to it and its annotations
+#[derive(Clone)]
+pub struct Node {
+    game: Game,
+    chess_move: Option<Move>,
+    parent: Option<NodeId>,
+    // The first child continues the line, the others are variations
+    children: Vec<NodeId>,
+    // Numeric Annotation Glyphs like $1 for a good move
+    pub nags: Vec<u8>,
+    // Comments shown before the move when it starts a line, and after the move
+    pub comments_before: Vec<String>,
+    pub comments: Vec<String>,
+}
+
+impl Node {
+    /// The game after the move of this node, with the moves that led to it as its history
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// The move that led to this node, None for the root
+    pub fn chess_move(&self) -> Option<&Move> {
+        self.chess_move.as_ref()
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    /// The moves played from this position, the first one continues the line and the others are variations
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+/// A game with side variations. Every node holds a position and the move that led to it, the
+/// main line follows the first child of every node.
+#[derive(Clone)]
+pub struct GameTree {
+    // Deleted nodes leave an empty slot, so ids of other nodes don't change
+    nodes: Vec<Option<Node>>,
+}
+
+impl GameTree {
+    /// Creates a tree with the current position of the game as its root. The tags of the game
+    /// are used when the tree is written as PGN.
+    pub fn new(game: Game) -> GameTree {
+        GameTree {
+            nodes: vec![Some(Node {
+                game,
+                chess_move: None,
+                parent: None,
+                children: Vec::new(),
+                nags: Vec::new(),
+                comments_before: Vec::new(),
+                comments: Vec::new(),
+            })],
+        }
+    }
+
+    /// Creates a tree from a game read with `read_pgn`, with the variations as side lines. The
+    /// result at the end of the movetext is kept as the "Result" tag of the root. Games from
+    /// `read_pgn` always load, a `PgnGame` put together by hand fails on its first illegal move.
+    pub fn from_pgn(pgn_game: &PgnGame) -> Result<GameTree, ChessError> {
+        let mut start = pgn_game.game.clone();
+        while start.unmake_move().is_some() {}
+
+        start.set_tag("Result", &pgn_game.result);
+
+        let mut tree = GameTree::new(start);
+        tree.add_pgn_moves(tree.root(), &pgn_game.moves)?;
+
+        Ok(tree)
+    }
+
+    fn add_pgn_moves(&mut self, parent: NodeId, moves: &[PgnMove]) -> Result<(), ChessError> {
+        let mut current = parent;
+
+        for pgn_move in moves {
+            let id = self.add_move(current, &pgn_move.chess_move)?;
+
+            if let Some(node) = self.node_mut(id) {
+                node.nags.extend(&pgn_move.nags);
+                node.comments_before.extend(pgn_move.comments_before.iter().cloned());
+                node.comments.extend(pgn_move.comments.iter().cloned());
+            }
+
+            for variation in &pgn_move.variations {
+                self.add_pgn_moves(current, variation)?;
+            }
+
+            current = id;
+        }
+
+        Ok(())
+    }
+
+    pub fn root(&self) -> NodeId {
+        0
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id)?.as_ref()
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id)?.as_mut()
+    }
+
+    /// Sets a PGN tag on the root game, see `Game::set_tag`
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        if let Some(root) = self.node_mut(0) {
+            root.game.set_tag(name, value);
+        }
+    }
+
+    /// Plays a move in the position of the parent node and returns the new node. The first move
+    /// from a position continues the line, later ones become variations. When the move was already
+    /// played from this position, its existing node is returned.
+    pub fn add_move(&mut self, parent: NodeId, chess_move: &Move) -> Result<NodeId, ChessError> {
+        let parent_node = self.node(parent).ok_or(ChessError::UNKNOWN_NODE(parent))?;
+
+        let mut game = parent_node.game.clone();
+        make_move(&mut game, chess_move)?;
+
+        let chess_move = *game.current_move().unwrap();
+
+        for child in &parent_node.children {
+            if self.node(*child).and_then(|node| node.chess_move) == Some(chess_move) {
+                return Ok(*child);
+            }
+        }
+
+        let id = self.nodes.len();
+
+        self.nodes.push(Some(Node {
+            game,
+            chess_move: Some(chess_move),
+            parent: Some(parent),
+            children: Vec::new(),
+            nags: Vec::new(),
+            comments_before: Vec::new(),
+            comments: Vec::new(),
+        }));
+
+        if let Some(parent_node) = self.node_mut(parent) {
+            parent_node.children.push(id);
+        }
+
+        Ok(id)
+    }
+
+    /// The nodes of the main line after the root
+    pub fn main_line(&self) -> Vec<NodeId> {
+        let mut line: Vec<NodeId> = Vec::new();
+        let mut current = self.root();
+
+        while let Some(child) = self.node(current).and_then(|node| node.children.first()) {
+            line.push(*child);
+            current = *child;
+        }
+
+        line
+    }
+
+    /// The nodes from the root to the given node, both included
+    pub fn path(&self, id: NodeId) -> Vec<NodeId> {
+        let mut path: Vec<NodeId> = Vec::new();
+        let mut current = self.node(id).map(|_| id);
+
+        while let Some(node_id) = current {
+            path.push(node_id);
+            current = self.node(node_id).and_then(|node| node.parent);
+        }
+
+        path.reverse();
+        path
+    }
+
+    // The parent of a node and the place of the node among its children
+    fn sibling_index(&self, id: NodeId) -> Option<(NodeId, usize)> {
+        let parent = self.node(id)?.parent?;
+        let index = self.node(parent)?.children.iter().position(|child| *child == id)?;
+
+        Some((parent, index))
+    }
+
+    /// Moves a variation one place up among its siblings, the first place is the main line.
+    /// Returns false when the node already is first or doesn't exist.
+    pub fn promote(&mut self, id: NodeId) -> bool {
+        match self.sibling_index(id) {
+            Some((parent, index)) if index > 0 => {
+                self.nodes[parent].as_mut().unwrap().children.swap(index, index - 1);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Moves a node one place down among its siblings. Returns false when the node already is last.
+    pub fn demote(&mut self, id: NodeId) -> bool {
+        match self.sibling_index(id) {
+            Some((parent, index)) if index + 1 < self.nodes[parent].as_ref().unwrap().children.len() => {
+                self.nodes[parent].as_mut().unwrap().children.swap(index, index + 1);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Makes the line through the node the main line, all the way from the root
+    pub fn make_main_line(&mut self, id: NodeId) {
+        let mut current = id;
+
+        while let Some((parent, index)) = self.sibling_index(current) {
+            let children = &mut self.nodes[parent].as_mut().unwrap().children;
+            let child = children.remove(index);
+            children.insert(0, child);
+
+            current = parent;
+        }
+    }
+
+    /// Deletes a node with everything played after it. The root can't be deleted.
+    pub fn delete(&mut self, id: NodeId) -> bool {
+        let (parent, index) = match self.sibling_index(id) {
+            Some(place) => place,
+            None => return false,
+        };
+
+        self.nodes[parent].as_mut().unwrap().children.remove(index);
+
+        let mut removed = vec![id];
+
+        while let Some(node_id) = removed.pop() {
+            if let Some(node) = self.nodes[node_id].take() {
+                removed.extend(node.children);
+            }
+        }
+
+        true
+    }
+
+    /// Writes the tree as PGN with the variations in parentheses. The tags come from the root
+    /// game. The result comes from the end of the main line when the game is over there, and
+    /// otherwise from the "Result" tag of the root, see `Game::result`.
+    pub fn to_pgn(&self) -> String {
+        let root = self.node(self.root()).unwrap();
+        let end = self.main_line().last().map_or(root, |id| self.node(*id).unwrap());
+        let result = if end.game.is_game_over() { end.game.result() } else { root.game.result() };
+
+        let mut tokens: Vec<String> = Vec::new();
+        self.write_line(self.root(), true, &mut tokens);
+        tokens.push(result.to_string());
+
+        write_pgn(&root.game, &root.game, result, &tokens)
+    }
+
+    // Writes the line that continues from a node, with the variations after each move
+    fn write_line(&self, from: NodeId, mut needs_number: bool, tokens: &mut Vec<String>) {
+        let mut current = from;
+
+        while let Some(children) = self.node(current).map(|node| node.children.as_slice()).filter(|children| !children.is_empty()) {
+            needs_number = self.write_move(children[0], needs_number, tokens);
+
+            // The parentheses are attached to the first and last token of the variation
+            for variation in &children[1..] {
+                let start = tokens.len();
+                let variation_needs_number = self.write_move(*variation, true, tokens);
+                self.write_line(*variation, variation_needs_number, tokens);
+
+                tokens[start].insert(0, '(');
+                tokens.last_mut().unwrap().push(')');
+
+                needs_number = true;
+            }
+
+            current = children[0];
+        }
+    }
+
+    // Writes one move with its number and annotations. Returns whether the next move needs
+    // its number written, which is the case after a comment.
+    fn write_move(&self, id: NodeId, needs_number: bool, tokens: &mut Vec<String>) -> bool {
+        let node = self.node(id).unwrap();
+        let before = &self.node(node.parent.unwrap()).unwrap().game;
+        let chess_move = node.chess_move.unwrap();
+
+        for comment in &node.comments_before {
+            push_comment(comment, tokens);
+        }
+
+        if before.side_to_move() == WHITE {
+            tokens.push(format!("{}.", before.fullmove_number()));
+        }
+
+        else if needs_number || !node.comments_before.is_empty() {
+            tokens.push(format!("{}...", before.fullmove_number()));
+        }
+
+        tokens.push(before.to_san(&chess_move).unwrap_or_else(|_| chess_move.to_uci()));
+
+        for nag in &node.nags {
+            tokens.push(format!("${}", nag));
+        }
+
+        for comment in &node.comments {
+            push_comment(comment, tokens);
+        }
+
+        !node.comments.is_empty()
+    }
+}
+
+// A comment is written word by word, so long comments can be wrapped
+fn push_comment(comment: &str, tokens: &mut Vec<String>) {
+    let words: Vec<&str> = comment.split_whitespace().collect();
+
+    match words.as_slice() {
+        [] => tokens.push(String::from("{}")),
+        [word] => tokens.push(format!("{{{}}}", word)),
+        [first, middle @ .., last] => {
+            tokens.push(format!("{{{}", first));
+            tokens.extend(middle.iter().map(|word| word.to_string()));
+            tokens.push(format!("{}}}", last));
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::{find_move, make_move, ChessError, Game, Move, PieceKind, Position};
+
+impl Move {
+    /// Writes the move in the long algebraic notation of the UCI protocol, like "e2e4", "e7e8q"
+    /// or "e1g1" for castling
+    pub fn to_uci(&self) -> String {
+        let mut uci = format!("{}{}", self.from, self.to);
+
+        if let Some(promotion) = self.promotion {
+            uci.push(promotion.kind.to_char().to_ascii_lowercase());
+        }
+
+        uci
+    }
+}
+
+impl Game {
+    /// Reads a move in UCI notation and looks it up among the legal moves of the position
+    pub fn parse_uci(&self, uci: &str) -> Result<Move, ChessError> {
+        let invalid = || ChessError::INVALID_UCI(uci.to_string());
+
+        if !uci.is_ascii() || (uci.len() != 4 && uci.len() != 5) {
+            return Err(invalid());
+        }
+
+        let start = Position::from_algebraic(&uci[0..2]).ok_or_else(invalid)?;
+        let end = Position::from_algebraic(&uci[2..4]).ok_or_else(invalid)?;
+
+        let promotion = match uci[4..].chars().next() {
+            Some(symbol @ ('q' | 'r' | 'b' | 'n')) => PieceKind::from_char(symbol),
+            Some(_) => return Err(invalid()),
+            None => None,
+        };
+
+        find_move(self, &start, &end, promotion)
+    }
+
+    /// Makes a move given in UCI notation, see `parse_uci`
+    pub fn make_uci_move(&mut self, uci: &str) -> Result<bool, ChessError> {
+        let chess_move = self.parse_uci(uci)?;
+
+        make_move(self, &chess_move)
+    }
+}
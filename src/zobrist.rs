@@ -0,0 +1,90 @@
+use crate::{can_capture_en_passant, Game, Piece, Position};
+use crate::Color::BLACK;
+
+// 12 pieces on 64 squares, then the side to move, the four castling rights and the eight en passant files
+const PIECE_KEYS: usize = 12 * 64;
+const SIDE_KEY: usize = PIECE_KEYS;
+const CASTLING_KEYS: usize = SIDE_KEY + 1;
+const EN_PASSANT_KEYS: usize = CASTLING_KEYS + 4;
+const KEY_COUNT: usize = EN_PASSANT_KEYS + 8;
+
+// The keys are generated from a fixed seed when compiling, so a hash stays the same across runs
+// and can be stored
+const SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+static KEYS: [u64; KEY_COUNT] = generate_keys();
+
+// SplitMix64, returns the next state and the random number
+const fn split_mix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_keys() -> [u64; KEY_COUNT] {
+    let mut keys = [0; KEY_COUNT];
+    let mut state = SEED;
+    let mut index = 0;
+
+    while index < KEY_COUNT {
+        let (next_state, key) = split_mix(state);
+        keys[index] = key;
+        state = next_state;
+        index += 1;
+    }
+
+    keys
+}
+
+/// The key for a piece standing on a square
+pub(crate) fn piece(piece: Piece, position: &Position) -> u64 {
+    let piece_index = piece.kind as usize * 2 + piece.color as usize;
+
+    KEYS[piece_index * 64 + position.rank * 8 + position.file]
+}
+
+/// The key that is added when black is to move
+pub(crate) fn side() -> u64 {
+    KEYS[SIDE_KEY]
+}
+
+/// The combined keys of the castling rights the game still has
+pub(crate) fn castling(game: &Game) -> u64 {
+    let rights = [game.white_castle_short, game.white_castle_long, game.black_castle_short, game.black_castle_long];
+
+    rights
+        .iter()
+        .enumerate()
+        .filter(|(_, right)| **right)
+        .fold(0, |hash, (index, _)| hash ^ KEYS[CASTLING_KEYS + index])
+}
+
+/// The key for the en passant file, which only counts when the side to move can actually capture
+pub(crate) fn en_passant(game: &Game) -> u64 {
+    match game.en_passant_possible {
+        Some(target) if can_capture_en_passant(game, &target) => KEYS[EN_PASSANT_KEYS + target.file],
+        _ => 0,
+    }
+}
+
+/// Computes the hash of a game from scratch, `make_move` keeps it up to date after that
+pub(crate) fn hash(game: &Game) -> u64 {
+    let mut hash = castling(game) ^ en_passant(game);
+
+    if game.side_to_move == BLACK {
+        hash ^= side();
+    }
+
+    for (rank, row) in game.board.board.iter().enumerate() {
+        for (file, square) in row.iter().enumerate() {
+            if let Some(square_piece) = square {
+                hash ^= piece(*square_piece, &Position::create(rank, file));
+            }
+        }
+    }
+
+    hash
+}